@@ -0,0 +1,17 @@
+--- ============================ review log ============================
+--- append only history of every scored review. one row per `review score`.
+create table review_log (
+    id integer primary key,
+    item_id integer not null,                            -- the reviewed item
+    grade integer not null,                              -- 1 again, 2 hard, 3 good, 4 easy
+    review_date text not null default current_timestamp, -- date in iso8601
+    elapsed_days real not null,                          -- number of days since the previous review of the item
+    stability_before real not null,                      -- sra parameter before the review
+    difficulty_before real not null,                     -- sra parameter before the review
+    stability_after real not null,                       -- sra parameter after the review
+    difficulty_after real not null,                      -- sra parameter after the review
+    created_at text not null default current_timestamp,  -- metadata
+    foreign key(item_id) references item(id) on delete cascade
+);
+create index review_log__item_id on review_log(item_id);
+--- --------------------------------------------------------------------------
@@ -0,0 +1,22 @@
+--- ============================ due date ============================
+--- the point in time at which an item should be reviewed again. derived from the
+--- stability and the desired retention when the item is scored.
+alter table item add column due_date text; -- sra parameter. date in iso8601, null for new items
+--- up until now items were due once their stability had passed, i.e. at 90% retention
+update item set due_date = datetime(last_review_date, '+' || stability || ' days') where maturity != 'new';
+--- --------------------------------------------------------------------------
+
+
+--- ============================ due items ============================
+drop view due_item;
+create view due_item as
+select
+    *
+from
+    item
+where
+    maturity != 'new' and
+    date(due_date) <= date('now')
+order by
+    stability asc;
+--- --------------------------------------------------------------------------
@@ -0,0 +1,21 @@
+--- ============================ learning steps ============================
+--- new and forgotten items go through a few short steps before they are scheduled in days
+alter table item add column learning_step integer not null default 0; -- sra parameter. current step of a learning or relearning item
+--- --------------------------------------------------------------------------
+
+
+--- ============================ due items ============================
+--- (re)learning items are due at their due time, other items on their due day
+drop view due_item;
+create view due_item as
+select
+    *
+from
+    item
+where
+    (maturity in ('learning', 'relearning') and datetime(due_date) <= datetime('now')) or
+    (maturity in ('young', 'tenured') and date(due_date) <= date('now'))
+order by
+    maturity in ('learning', 'relearning') desc,
+    stability asc;
+--- --------------------------------------------------------------------------
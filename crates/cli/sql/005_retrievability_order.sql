@@ -0,0 +1,16 @@
+--- ============================ due items ============================
+--- due items that have been scheduled are shown in order of their probability of recall,
+--- the ones most likely to be forgotten first. `retrievability` is registered on open.
+drop view due_item;
+create view due_item as
+select
+    *
+from
+    item
+where
+    (maturity in ('learning', 'relearning') and datetime(due_date) <= datetime('now')) or
+    (maturity in ('young', 'tenured') and date(due_date) <= date('now'))
+order by
+    maturity in ('learning', 'relearning') desc,
+    retrievability(stability, last_review_date) asc;
+--- --------------------------------------------------------------------------
@@ -0,0 +1,36 @@
+--- ============================ suspended items ============================
+--- suspended items keep their scheduling data, but are never shown for review
+alter table item add column suspended integer not null default 0; -- whether the item is left out of reviews
+--- --------------------------------------------------------------------------
+
+
+--- ============================ due items ============================
+drop view due_item;
+create view due_item as
+select
+    *
+from
+    item
+where
+    not suspended and (
+        (maturity in ('learning', 'relearning') and datetime(due_date) <= datetime('now')) or
+        (maturity in ('young', 'tenured') and date(due_date) <= date('now'))
+    )
+order by
+    maturity in ('learning', 'relearning') desc,
+    retrievability(stability, last_review_date) asc;
+--- --------------------------------------------------------------------------
+
+
+--- ============================ new items ============================
+drop view new_item;
+create view new_item as
+select
+    *
+from
+    item
+where
+    maturity == 'new' and not suspended
+order by
+    last_review_date, created_at;
+--- --------------------------------------------------------------------------
@@ -0,0 +1,39 @@
+--- ============================ buried items ============================
+--- buried items are left out of reviews until some time has passed, e.g. the next day
+alter table item add column buried_until text; -- date in iso8601 until which the item is left out of reviews, null if not buried
+--- --------------------------------------------------------------------------
+
+
+--- ============================ due items ============================
+drop view due_item;
+create view due_item as
+select
+    *
+from
+    item
+where
+    not suspended and
+    (buried_until is null or datetime(buried_until) <= datetime('now')) and (
+        (maturity in ('learning', 'relearning') and datetime(due_date) <= datetime('now')) or
+        (maturity in ('young', 'tenured') and date(due_date) <= date('now'))
+    )
+order by
+    maturity in ('learning', 'relearning') desc,
+    retrievability(stability, last_review_date) asc;
+--- --------------------------------------------------------------------------
+
+
+--- ============================ new items ============================
+drop view new_item;
+create view new_item as
+select
+    *
+from
+    item
+where
+    maturity == 'new' and
+    not suspended and
+    (buried_until is null or datetime(buried_until) <= datetime('now'))
+order by
+    last_review_date, created_at;
+--- --------------------------------------------------------------------------
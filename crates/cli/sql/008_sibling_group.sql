@@ -0,0 +1,5 @@
+--- ============================ sibling groups ============================
+--- items created from the same source, e.g. the front and back of a flashcard, share a group
+alter table item add column sibling_group text; -- items with the same group are siblings, null if the item has none
+create index item__sibling_group on item(sibling_group);
+--- --------------------------------------------------------------------------
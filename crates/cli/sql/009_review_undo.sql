@@ -0,0 +1,11 @@
+--- ============================ undo ============================
+--- the scheduling state of the item before a review, such that the review can be undone.
+--- null for reviews that were logged before these were kept.
+alter table review_log add column maturity_before text;
+alter table review_log add column last_review_date_before text;
+alter table review_log add column n_reviews_before integer;
+alter table review_log add column n_lapses_before integer;
+alter table review_log add column learning_step_before integer;
+alter table review_log add column due_date_before text;
+alter table review_log add column suspended_before integer;
+--- --------------------------------------------------------------------------
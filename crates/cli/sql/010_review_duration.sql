@@ -0,0 +1,3 @@
+--- ============================ review duration ============================
+alter table review_log add column duration_ms integer; -- how long the recall took in milliseconds, null if unknown
+--- --------------------------------------------------------------------------
@@ -0,0 +1,19 @@
+--- ============================ due items ============================
+--- scheduled items are due from the start of the study day of their due date, which need not
+--- begin at midnight utc
+drop view due_item;
+create view due_item as
+select
+    *
+from
+    item
+where
+    not suspended and
+    (buried_until is null or datetime(buried_until) <= datetime('now')) and (
+        (maturity in ('learning', 'relearning') and datetime(due_date) <= datetime('now')) or
+        (maturity in ('young', 'tenured') and study_date(due_date) <= study_date())
+    )
+order by
+    maturity in ('learning', 'relearning') desc,
+    retrievability(stability, last_review_date) asc;
+--- --------------------------------------------------------------------------
@@ -0,0 +1,22 @@
+--- ============================ study sessions ============================
+--- a snapshot of the review queue, served in order until the session ends. at most one
+--- session is open at a time.
+create table session (
+    id integer primary key,
+    started_at text not null default current_timestamp, -- date in iso8601
+    ended_at text                                       -- date in iso8601, null while the session is open
+);
+create table session_item (
+    id integer primary key,
+    session_id integer not null,
+    item_id integer not null,
+    position integer not null,  -- items are served in order of their position
+    review_id integer,          -- the review of the item in this session, null until it is scored
+    again_review_id integer,    -- the failed review that put the item back in the queue, null for the snapshot
+    foreign key(session_id) references session(id) on delete cascade,
+    foreign key(item_id) references item(id) on delete cascade,
+    foreign key(review_id) references review_log(id) on delete set null,
+    foreign key(again_review_id) references review_log(id) on delete cascade
+);
+create index session_item__session_id on session_item(session_id, position);
+--- --------------------------------------------------------------------------
@@ -0,0 +1,3 @@
+--- ============================ leech undo ============================
+alter table review_log add column leech_tag text; -- the leech tag given to the item by the review, removed again when it is undone
+--- --------------------------------------------------------------------------
@@ -37,6 +37,12 @@ pub enum Command {
     /// Review the items
     #[command(subcommand)]
     Review(ReviewCommand),
+    /// Tune the spaced repetition algorithm
+    #[command(subcommand)]
+    Sra(SraCommand),
+    /// Statistics of the collection
+    #[command(subcommand)]
+    Stats(StatsCommand),
 }
 
 #[derive(Subcommand, Debug)]
@@ -52,6 +58,9 @@ pub enum ItemCommand {
         /// A list of tags delimited by ' ' that should be associated with the item.
         #[clap(long,value_delimiter=' ', num_args=1..)]
         tags: Vec<String>,
+        /// The sibling group of the item, shared by items made from the same source
+        #[clap(long)]
+        group: Option<String>,
     },
     /// Edit a review item
     Edit {
@@ -69,6 +78,9 @@ pub enum ItemCommand {
         /// A list of tags delimited by ' ' that should no longer be associated with the item.
         #[clap(long)]
         remove_tags: Vec<String>,
+        /// The new sibling group of the item, '' to remove it from its group
+        #[clap(long)]
+        group: Option<String>,
     },
     /// Delete a review item
     Delete {
@@ -100,10 +112,50 @@ pub enum ItemCommand {
         /// Filter items that do not contain tags
         #[arg(long)]
         exclude_tags: Vec<String>,
+        /// Order the items, `review.query_order` if left out, which lists them by id by
+        /// default
+        #[arg(long, value_enum)]
+        order: Option<crate::queries::review::Order>,
         /// Whether to pretty print output
         #[arg(long, default_value_t = false)]
         pretty: bool,
     },
+    /// Leave items out of reviews until they are unsuspended
+    Suspend(ItemSelection),
+    /// Include suspended items in reviews again
+    Unsuspend(ItemSelection),
+    /// Leave items out of reviews for some days
+    Bury {
+        #[command(flatten)]
+        items: ItemSelection,
+        /// The items are reviewed again from the start of the day this many days from now
+        #[arg(long, default_value_t = 1)]
+        days: u32,
+    },
+    /// Include buried items in reviews again
+    Unbury(ItemSelection),
+    /// List the leeches, items that were forgotten at least `leech.threshold` times
+    Leeches {
+        #[arg(long, value_parser = parser::ast_node)]
+        /// querying logic applied before handling the json result
+        pre_filter: Option<AstNode>,
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
+    },
+}
+
+/// The items that a command applies to, either a single item or all items matching a filter
+#[derive(Args, Debug)]
+pub struct ItemSelection {
+    /// id of the item
+    #[arg(required_unless_present = "pre_filter", conflicts_with = "pre_filter")]
+    pub id: Option<i32>,
+    #[arg(long, value_parser = parser::ast_node)]
+    /// All items matching this filter
+    pub pre_filter: Option<AstNode>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -114,6 +166,31 @@ pub enum ReviewCommand {
     /// Return how many many items are due
     #[command(subcommand)]
     QueryCount(QueryCountCommand),
+    /// A batch of items to study: (re)learning items first, followed by due and new items
+    /// placed by `review.new_placement`
+    Queue {
+        /// The number of items
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long, value_parser = parser::ast_node)]
+        /// querying logic applied before handling the json result
+        pre_filter: Option<AstNode>,
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
+        /// Where new items go among the due items, `review.new_placement` if left out
+        #[arg(long, value_enum)]
+        new_placement: Option<crate::config::NewPlacement>,
+        /// With mixed placement, one new item follows this many due items,
+        /// `review.new_every` if left out
+        #[arg(long)]
+        new_every: Option<u32>,
+    },
+    /// Study sessions, serving a snapshot of the review queue in order
+    #[command(subcommand)]
+    Session(SessionCommand),
     /// score how well the review of an item went
     Score {
         /// id of the item
@@ -121,6 +198,74 @@ pub enum ReviewCommand {
         /// "again", "hard", "good", "easy"
         #[arg(value_parser = parser::grade)]
         grade: sra::model::Grade,
+        /// When the review took place, e.g. 2024-01-31T18:00:00+01:00. Now if left out
+        #[arg(long, value_parser = parser::timestamp)]
+        at: Option<OffsetDateTime>,
+        /// How long the recall took, in milliseconds
+        #[arg(long)]
+        duration_ms: Option<u32>,
+    },
+    /// Show the outcome of each grade without scoring the item
+    Preview {
+        /// id of the item
+        id: i32,
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
+    },
+    /// Undo the most recent score, restoring the item to its state from before the review
+    Undo {
+        /// Undo the most recent score of this item instead
+        #[arg(long)]
+        id: Option<i32>,
+    },
+    /// List the history of scored reviews, oldest first
+    Log {
+        #[arg(long, value_parser = parser::ast_node)]
+        /// querying logic applied before handling the json result
+        pre_filter: Option<AstNode>,
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
+    },
+}
+#[derive(Subcommand, Debug)]
+pub enum SessionCommand {
+    /// Start a session with a snapshot of `review queue`
+    Start {
+        /// The number of items
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long, value_parser = parser::ast_node)]
+        /// querying logic applied before handling the json result
+        pre_filter: Option<AstNode>,
+        /// Where new items go among the due items, `review.new_placement` if left out
+        #[arg(long, value_enum)]
+        new_placement: Option<crate::config::NewPlacement>,
+        /// With mixed placement, one new item follows this many due items,
+        /// `review.new_every` if left out
+        #[arg(long)]
+        new_every: Option<u32>,
+    },
+    /// The next item of the session that has not been scored
+    Next {
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
+    },
+    /// End the session and summarize what it accomplished
+    End {
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
     },
 }
 #[derive(Subcommand, Debug)]
@@ -147,6 +292,9 @@ pub enum NextReviewCommand {
         post_filter: Option<String>,
         #[arg(long, default_value_t = false)]
         pretty: bool,
+        /// The order of the new items, `review.new_order` if left out
+        #[arg(long, value_enum)]
+        order: Option<crate::queries::review::Order>,
     },
     Due {
         #[arg(long, value_parser = parser::ast_node)]
@@ -157,6 +305,78 @@ pub enum NextReviewCommand {
         post_filter: Option<String>,
         #[arg(long, default_value_t = false)]
         pretty: bool,
+        /// The order of the due items, `review.due_order` if left out
+        #[arg(long, value_enum)]
+        order: Option<crate::queries::review::Order>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SraCommand {
+    /// Fit the algorithm weights to the review history
+    Optimize {
+        #[arg(long, value_parser = parser::ast_node)]
+        /// Only train on the review log entries matching this filter
+        pre_filter: Option<AstNode>,
+        /// Number of gradient descent steps
+        #[arg(long, default_value_t = 200)]
+        epochs: usize,
+        /// Save the fitted weights to .spbased/config.toml
+        #[arg(long, default_value_t = false)]
+        save: bool,
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
+    },
+    /// Recompute the due dates of items, after the weights, the desired retention or the
+    /// interval bounds were changed
+    Reschedule {
+        #[arg(long, value_parser = parser::ast_node)]
+        /// Only reschedule items matching this filter
+        pre_filter: Option<AstNode>,
+        /// Replay the review history of each item, recomputing its stability and
+        /// difficulty too. Otherwise only the due dates are derived from the current state.
+        #[arg(long, default_value_t = false)]
+        replay: bool,
+        /// Only report how many due dates would move
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StatsCommand {
+    /// Project the number of reviews on each of the coming days, starting today
+    Forecast {
+        /// Number of days to forecast
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+        /// Number of new items that are planned to be learnt each day
+        #[arg(long, default_value_t = 0)]
+        new_per_day: u32,
+        /// The model of the planned new items, which decides their scheduling
+        #[arg(long)]
+        new_model: Option<String>,
+        /// Simulate whether items are recalled, averaged over this many runs. Otherwise
+        /// every review is assumed to be graded good
+        #[arg(long)]
+        simulate: Option<u32>,
+        /// Seed of the simulation
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[arg(long, value_parser = parser::ast_node)]
+        /// Only forecast the items matching this filter
+        pre_filter: Option<AstNode>,
+        /// Print an ascii bar chart instead of json
+        #[arg(long, default_value_t = false)]
+        chart: bool,
+        #[arg(long)]
+        /// Fine grained json based filtering. Uses <https://jmespath.org/>
+        post_filter: Option<String>,
+        #[arg(long, default_value_t = false)]
+        pretty: bool,
     },
 }
 
@@ -193,6 +413,11 @@ pub mod parser {
         }
     }
 
+    pub fn timestamp(s: &str) -> Result<OffsetDateTime, String> {
+        OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339)
+            .map_err(|e| format!("expected an rfc3339 timestamp: {e}"))
+    }
+
     pub fn ast_node(s: &str) -> Result<AstNode, String> {
         filter_language::FilterLangParser::parse(s).map_err(|e| e.to_string())
     }
@@ -249,7 +474,7 @@ pub mod filter_language {
         static FILTER_PARSER: LazyLock<PrattParser<Rule>> = LazyLock::new(|| {
             use pest::pratt_parser::{Assoc::*, Op};
             use Rule::*;
-            let parser = PrattParser::new()
+            PrattParser::new()
                 .op(Op::infix(or, Left))
                 .op(Op::infix(and, Left))
                 .op(Op::infix(eq, Left)
@@ -257,8 +482,7 @@ pub mod filter_language {
                     | Op::infix(le, Left)
                     | Op::infix(leq, Left)
                     | Op::infix(ge, Left)
-                    | Op::infix(geq, Left));
-            parser
+                    | Op::infix(geq, Left))
         });
         FILTER_PARSER
             .map_primary(|p| match p.as_rule() {
@@ -433,5 +657,21 @@ pub mod filter_language {
                 ),
             );
         }
+
+        #[test]
+        fn test_parse_comparisons() {
+            test_ast_node_parser(
+                "retrievability <= 0.85",
+                AstNode::comparative_filter("retrievability", Leq, AstNode::float(0.85)),
+            );
+            test_ast_node_parser(
+                "n_reviews >= 10",
+                AstNode::comparative_filter("n_reviews", Geq, AstNode::integer(10)),
+            );
+            test_ast_node_parser(
+                "elapsed_days > -1.5",
+                AstNode::comparative_filter("elapsed_days", Ge, AstNode::float(-1.5)),
+            );
+        }
     }
 }
@@ -1,5 +1,5 @@
 //! Spbasedctl implementation
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use eyre::eyre;
 use eyre::Result;
 use eyre::WrapErr;
@@ -9,8 +9,8 @@ use rusqlite::params;
 use rusqlite::params_from_iter;
 use rusqlite::Connection;
 use serde_json::json;
-use std::cell::LazyCell;
 use std::path::PathBuf;
+use std::sync::LazyLock;
 use time::OffsetDateTime;
 
 pub mod cli;
@@ -19,11 +19,11 @@ pub mod queries;
 use cli::*;
 use db::DB;
 
-pub const APP_NAME: &'static str = "spbased";
-pub const DEFAULT_APP_CONFIG_DIR: LazyCell<PathBuf> =
-    LazyCell::new(|| dirs::config_dir().unwrap().join(APP_NAME));
-pub const DB_NAME: &'static str = "db.sqlite";
-pub const CONFIG_NAME: &'static str = "config.toml";
+pub const APP_NAME: &str = "spbased";
+pub static DEFAULT_APP_CONFIG_DIR: LazyLock<PathBuf> =
+    LazyLock::new(|| dirs::config_dir().unwrap().join(APP_NAME));
+pub const DB_NAME: &str = "db.sqlite";
+pub const CONFIG_NAME: &str = "config.toml";
 
 // ======= CLI COMMAND HANDLERS BEGIN ======
 pub fn handle_command(root: Option<PathBuf>, command: Command) -> Result<Option<String>> {
@@ -35,14 +35,16 @@ pub fn handle_command(root: Option<PathBuf>, command: Command) -> Result<Option<
         }
         command => {
             let root = config::AppRoot::try_resolve(root)?;
-            log::debug!("spbased root set to {:?}", &root);
+            log::debug!("spbased root set to {:?}", root);
             let config = config::AppConfig::resolve(root)?;
-            log::debug!("spbased config set to {:?}", &config);
-            let db = DB::open(&config.db_path)?;
+            log::debug!("spbased config set to {:?}", config);
+            let db = DB::open(&config.db_path, &config.sra, config.study_day)?;
             match command {
-                Command::Items(command) => command::item::handle_command(db, command)?,
-                Command::Review(command) => command::review::handle_command(db, command)?,
+                Command::Items(command) => command::item::handle_command(db, &config, command)?,
+                Command::Review(command) => command::review::handle_command(db, &config, command)?,
                 Command::Tags(command) => command::tag::handle_command(db, command)?,
+                Command::Sra(command) => command::algorithm::handle_command(db, &config, command)?,
+                Command::Stats(command) => command::stats::handle_command(db, &config, command)?,
                 _ => unreachable!(),
             }
         }
@@ -51,11 +53,18 @@ pub fn handle_command(root: Option<PathBuf>, command: Command) -> Result<Option<
 
 pub mod config {
     use super::*;
+    use crate::queries::review::{Order, Studied};
     use figment::{
         providers::{Env, Format, Serialized, Toml},
         Figment,
     };
+    use model::{ItemModel, TagName};
     use serde::{Deserialize, Serialize};
+    use sra::day::StudyDay;
+    use sra::load_balance::{WeekLoad, EVEN_WEEK};
+    use sra::scheduler::{IntervalBounds, SchedulerKind};
+    use sra::Step;
+    use std::collections::BTreeMap;
     use std::path::{Path, PathBuf};
 
     use crate::APP_NAME;
@@ -112,11 +121,392 @@ pub mod config {
         pub app_root: PathBuf,
         #[serde(skip)]
         pub db_path: PathBuf,
+        /// The hour at which a new study day begins, e.g. 4 for 4am
+        #[serde(default)]
+        pub day_starts_at: u8,
+        /// The time zone of `day_starts_at`, e.g. "Asia/Tokyo". UTC if left out
+        #[serde(default)]
+        pub timezone: Option<String>,
+        /// The study day following from `day_starts_at` and `timezone`
+        #[serde(skip)]
+        pub study_day: StudyDay,
+        /// The `[sra]` table, parameters of the spaced repetition algorithm
+        #[serde(default)]
+        pub sra: sra::Parameters,
+        /// The `[scheduling]` table
+        #[serde(default)]
+        pub scheduling: SchedulingConfig,
+        /// The `[review]` table
+        #[serde(default)]
+        pub review: ReviewConfig,
+        /// The `[leech]` table
+        #[serde(default)]
+        pub leech: LeechConfig,
+        /// The `[limits]` table
+        #[serde(default)]
+        pub limits: LimitsConfig,
+    }
+
+    /// Settings deciding the order in which items are reviewed
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct ReviewConfig {
+        /// The order of `review next due`
+        pub due_order: Order,
+        /// The order of `review next new`
+        pub new_order: Order,
+        /// The order of `items query`, by id if left out
+        pub query_order: Option<Order>,
+        /// Whether to bury the siblings of a scored item until the next day
+        pub bury_siblings: bool,
+        /// Where the new items of `review queue` go among the due items
+        pub new_placement: NewPlacement,
+        /// With `mixed` new items, one new item follows this many due items
+        pub new_every: u32,
+        /// In a study session, an item graded again is shown again after this many items
+        pub again_offset: u32,
+    }
+
+    /// Where new items go among due items
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize, clap::ValueEnum)]
+    #[serde(rename_all = "lowercase")]
+    pub enum NewPlacement {
+        /// one new item every few due items
+        Mixed,
+        /// new items first
+        Before,
+        /// new items last
+        After,
+    }
+
+    impl NewPlacement {
+        /// `due` and `new` interleaved, with one new item following every `every` due items
+        /// when mixed. Both keep their order, and what is left of one follows the other.
+        pub fn interleave<T>(self, every: u32, due: Vec<T>, new: Vec<T>) -> Vec<T> {
+            let (mut due, mut new) = (due.into_iter().peekable(), new.into_iter());
+            match self {
+                NewPlacement::Before => new.chain(due).collect(),
+                NewPlacement::After => due.chain(new).collect(),
+                NewPlacement::Mixed => {
+                    let mut items = vec![];
+                    while due.peek().is_some() {
+                        items.extend(due.by_ref().take(every.max(1) as usize));
+                        items.extend(new.next());
+                    }
+                    items.extend(new);
+                    items
+                }
+            }
+        }
+    }
+
+    /// Settings deciding what happens to leeches, items that are forgotten again and again
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct LeechConfig {
+        /// The number of lapses at which an item becomes a leech, 0 to never
+        pub threshold: u32,
+        /// What happens to an item when it becomes a leech
+        pub action: LeechAction,
+        /// The tag of leeches, for the `tag` and `both` actions
+        pub tag: TagName,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum LeechAction {
+        Tag,
+        Suspend,
+        Both,
+    }
+
+    impl Default for LeechConfig {
+        fn default() -> Self {
+            Self {
+                threshold: 8,
+                action: LeechAction::Tag,
+                tag: "leech".into(),
+            }
+        }
+    }
+
+    impl LeechConfig {
+        /// Whether an item with `lapses` lapses is a leech
+        pub fn is_leech(&self, lapses: u32) -> bool {
+            self.threshold > 0 && lapses >= self.threshold
+        }
+
+        /// Whether an item that just lapsed for the `lapses`th time should be handled as a
+        /// leech. Leeches that were unsuspended are handled again every half threshold.
+        pub fn handle_lapse(&self, lapses: u32) -> bool {
+            let every = (self.threshold / 2).max(1);
+            self.is_leech(lapses) && (lapses - self.threshold).is_multiple_of(every)
+        }
+    }
+
+    /// Daily limits on the number of items studied. The limits of a tag
+    /// (`[limits.tags.<tag>]`) apply to the items with the tag, on top of the global ones.
+    #[derive(Default, Debug, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct LimitsConfig {
+        #[serde(flatten)]
+        pub all: Limits,
+        pub tags: BTreeMap<TagName, Limits>,
+    }
+
+    #[derive(Default, Debug, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct Limits {
+        /// The number of new items learnt per day, no limit if left out
+        pub new_per_day: Option<u32>,
+        /// The number of reviews of learnt items per day, no limit if left out.
+        /// (Re)learning items are not limited.
+        pub reviews_per_day: Option<u32>,
+    }
+
+    impl Limits {
+        /// How many more new items (`new`) or reviews may be studied after `studied`,
+        /// `None` if there is no limit
+        pub fn remaining(&self, studied: &Studied, new: bool) -> Option<u32> {
+            let (limit, n) = match new {
+                true => (self.new_per_day, studied.new),
+                false => (self.reviews_per_day, studied.reviews),
+            };
+            limit.map(|limit| limit.saturating_sub(n))
+        }
+    }
+
+    impl Default for ReviewConfig {
+        fn default() -> Self {
+            Self {
+                due_order: Order::Retrievability,
+                new_order: Order::Created,
+                query_order: None,
+                bury_siblings: true,
+                new_placement: NewPlacement::Mixed,
+                new_every: 5,
+                again_offset: 3,
+            }
+        }
+    }
+
+    /// Settings deciding when items are shown. Some of them may be overridden for
+    /// items of a certain model (`[scheduling.models.<model>]`) or with a certain tag
+    /// (`[scheduling.tags.<tag>]`).
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct SchedulingConfig {
+        /// The probability of recall at which an item becomes due
+        pub desired_retention: sra::model::Retrievability,
+        /// The algorithm used to schedule items
+        pub scheduler: SchedulerKind,
+        /// The boxes used by the leitner scheduler
+        pub leitner: sra::scheduler::Leitner,
+        /// Whether to spread out due dates by fuzzing the intervals
+        pub fuzz: bool,
+        /// Changes which day within its fuzz range an interval ends up on
+        pub fuzz_seed: u64,
+        /// The delays at which new items are shown before they are scheduled in days
+        pub learning_steps: Vec<Step>,
+        /// The delays at which forgotten items are shown before they are scheduled in days
+        pub relearning_steps: Vec<Step>,
+        /// The shortest interval, in days, of items that are not (re)learning
+        pub minimum_interval: sra::model::Interval,
+        /// The longest interval, in days, at most [`sra::S_MAX`]
+        pub maximum_interval: sra::model::Interval,
+        /// Whether to move intervals to the day with the fewest reviews in their fuzz range
+        pub load_balance: bool,
+        /// The relative amount of reviews on some weekdays, e.g. `sunday = 0.5`. Days that
+        /// are left out get the full amount of reviews.
+        pub easy_days: BTreeMap<String, f32>,
+        pub models: BTreeMap<ItemModel, SchedulingOverrides>,
+        pub tags: BTreeMap<TagName, SchedulingOverrides>,
+    }
+
+    impl Default for SchedulingConfig {
+        fn default() -> Self {
+            Self {
+                desired_retention: 0.9,
+                scheduler: SchedulerKind::default(),
+                leitner: Default::default(),
+                fuzz: true,
+                fuzz_seed: 0,
+                learning_steps: vec![
+                    Step(time::Duration::minutes(1)),
+                    Step(time::Duration::minutes(10)),
+                ],
+                relearning_steps: vec![Step(time::Duration::minutes(10))],
+                minimum_interval: IntervalBounds::default().minimum,
+                maximum_interval: IntervalBounds::default().maximum,
+                load_balance: true,
+                easy_days: BTreeMap::new(),
+                models: BTreeMap::new(),
+                tags: BTreeMap::new(),
+            }
+        }
+    }
+
+    /// The scheduling settings that may differ between models and tags
+    #[derive(Default, Debug, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct SchedulingOverrides {
+        pub desired_retention: Option<sra::model::Retrievability>,
+        pub scheduler: Option<SchedulerKind>,
+        pub learning_steps: Option<Vec<Step>>,
+        pub relearning_steps: Option<Vec<Step>>,
+    }
+
+    impl SchedulingConfig {
+        /// The overrides that apply to an item, most specific first: the override of
+        /// its model, followed by the overrides of its tags in alphabetical order.
+        fn overrides<'a>(
+            &'a self,
+            model: &str,
+            tags: &'a [&str],
+        ) -> impl Iterator<Item = &'a SchedulingOverrides> + 'a {
+            self.models.get(model).into_iter().chain(
+                self.tags
+                    .iter()
+                    .filter_map(|(tag, o)| tags.contains(&tag.as_str()).then_some(o)),
+            )
+        }
+
+        /// The desired retention of an item with the given model and tags
+        pub fn desired_retention(&self, model: &str, tags: &[&str]) -> sra::model::Retrievability {
+            self.overrides(model, tags)
+                .find_map(|o| o.desired_retention)
+                .unwrap_or(self.desired_retention)
+        }
+
+        /// The scheduler of an item with the given model and tags
+        pub fn scheduler(&self, model: &str, tags: &[&str]) -> SchedulerKind {
+            self.overrides(model, tags)
+                .find_map(|o| o.scheduler)
+                .unwrap_or(self.scheduler)
+        }
+
+        /// The (re)learning steps of an item with the given model and tags
+        pub fn steps(&self, model: &str, tags: &[&str]) -> sra::Steps {
+            let overrides: Vec<_> = self.overrides(model, tags).collect();
+            sra::Steps {
+                learning: overrides
+                    .iter()
+                    .find_map(|o| o.learning_steps.clone())
+                    .unwrap_or_else(|| self.learning_steps.clone()),
+                relearning: overrides
+                    .iter()
+                    .find_map(|o| o.relearning_steps.clone())
+                    .unwrap_or_else(|| self.relearning_steps.clone()),
+            }
+        }
+
+        pub fn bounds(&self) -> IntervalBounds {
+            IntervalBounds {
+                minimum: self.minimum_interval,
+                maximum: self.maximum_interval,
+            }
+        }
+
+        /// The scheduling settings of an item with the given model and tags, with days
+        /// beginning at midnight UTC. See [`AppConfig::settings`] for the configured days.
+        pub fn settings(&self, model: &str, tags: &[&str]) -> sra::scheduler::Settings {
+            sra::scheduler::Settings {
+                steps: self.steps(model, tags),
+                bounds: self.bounds(),
+                easy_days: self.week_load().unwrap_or(EVEN_WEEK),
+                day: StudyDay::default(),
+            }
+        }
+
+        /// The `easy_days` as the load of each weekday, Monday first
+        fn week_load(&self) -> Result<WeekLoad> {
+            let mut week = EVEN_WEEK;
+            for (day, load) in &self.easy_days {
+                // accept any casing, e.g. sunday
+                let i = (0..7)
+                    .find(|i| {
+                        time::Weekday::Monday
+                            .nth_next(*i)
+                            .to_string()
+                            .eq_ignore_ascii_case(day)
+                    })
+                    .ok_or_else(|| eyre!("`{day}` in easy_days is not a weekday"))?;
+                if !(0.0..=1.0).contains(load) {
+                    return Err(eyre!("the load of easy day `{day}` must lie in [0, 1]"));
+                }
+                week[i as usize] = *load;
+            }
+            Ok(week)
+        }
+
+        /// The fuzz factor of the next review of item `id`, which has been reviewed
+        /// `reps` times. `None` if fuzz is turned off.
+        pub fn fuzz_factor(&self, id: i32, reps: u32) -> Option<f32> {
+            self.fuzz
+                .then(|| sra::fuzz::factor(self.fuzz_seed, id as u64, reps))
+        }
+
+        pub fn validate(&self) -> Result<()> {
+            self.week_load()?;
+            let (min, max) = (self.minimum_interval, self.maximum_interval);
+            if !(min > 0.0 && min <= max) {
+                return Err(eyre!(
+                    "expected 0 < minimum_interval <= maximum_interval, got {min} and {max}"
+                ));
+            }
+            if max > sra::S_MAX {
+                return Err(eyre!(
+                    "maximum_interval may be at most {} days, got {max}",
+                    sra::S_MAX
+                ));
+            }
+            let intervals = &self.leitner.intervals;
+            if intervals.is_empty() || !intervals.iter().all(|i| *i > 0.0) {
+                return Err(eyre!(
+                    "leitner intervals must be a non-empty list of positive days"
+                ));
+            }
+            let retentions = std::iter::once(Some(self.desired_retention))
+                .chain(self.models.values().map(|o| o.desired_retention))
+                .chain(self.tags.values().map(|o| o.desired_retention))
+                .flatten();
+            for r in retentions {
+                if !(r > 0.0 && r < 1.0) {
+                    return Err(eyre!("desired_retention must lie in (0, 1), got {r}"));
+                }
+            }
+            Ok(())
+        }
     }
 
     impl AppConfig {
         pub fn new(app_root: PathBuf, db_path: PathBuf) -> Self {
-            Self { app_root, db_path }
+            Self {
+                app_root,
+                db_path,
+                ..Default::default()
+            }
+        }
+
+        /// The scheduling settings of an item with the given model and tags, in study days
+        pub fn settings(&self, model: &str, tags: &[&str]) -> sra::scheduler::Settings {
+            sra::scheduler::Settings {
+                day: self.study_day,
+                ..self.scheduling.settings(model, tags)
+            }
+        }
+
+        /// The scheduler of an item with the given model and tags
+        pub fn scheduler(&self, model: &str, tags: &[&str]) -> Box<dyn sra::Scheduler> {
+            use sra::scheduler::*;
+            match self.scheduling.scheduler(model, tags) {
+                SchedulerKind::Fsrs => Box::new(Fsrs {
+                    p: self.sra,
+                    desired_retention: self.scheduling.desired_retention(model, tags),
+                }),
+                SchedulerKind::Sm2 => Box::new(Sm2),
+                SchedulerKind::Leitner => Box::new(self.scheduling.leitner.clone()),
+            }
         }
     }
 
@@ -144,15 +534,307 @@ pub mod config {
             let figment = Figment::new()
                 .merge(Serialized::defaults(Self::default()))
                 .merge(Toml::file(config_file_path(&DEFAULT_APP_CONFIG_DIR))) // .config/markz
-                .merge(Env::prefixed(&format!("{APP_NAME}_").to_uppercase())) // from enviornment variables
+                .merge(Env::prefixed(&format!("{APP_NAME}_").to_uppercase()).split("__")) // from enviornment variables
                 .merge(Toml::file(config_file_path(&work_dir)));
 
             let mut config: Self = figment.extract()?;
             config.app_root = app_root;
             config.db_path = db_path;
 
+            config
+                .sra
+                .validate()
+                .wrap_err("invalid [sra] parameters in config")?;
+            if !config.sra.is_clamped() {
+                log::warn!("[sra] parameters outside of their legal range have been clamped");
+                config.sra = config.sra.clamped();
+            }
+            config
+                .scheduling
+                .validate()
+                .wrap_err("invalid [scheduling] settings in config")?;
+            let timezone = config.timezone.as_deref().unwrap_or("UTC");
+            config.study_day = StudyDay::new(timezone, config.day_starts_at).ok_or(eyre!(
+                "invalid study day in config, day_starts_at must be an hour from 0 to 23 and \
+                timezone a time zone such as \"Europe/Stockholm\", got {} and {timezone:?}",
+                config.day_starts_at
+            ))?;
+
             Ok(config)
         }
+
+        /// .spbased/config.toml of this collection
+        pub fn local_config_path(&self) -> PathBuf {
+            config_file_path(&app_work_dir(&self.app_root))
+        }
+
+        /// Write `weights` to the `[sra]` table of the local config file, keeping
+        /// any other settings in the file.
+        pub fn save_weights(&self, weights: &sra::Weights) -> Result<()> {
+            let path = self.local_config_path();
+            let mut table: toml::Table = match std::fs::read_to_string(&path) {
+                Ok(content) => content.parse().wrap_err("parsing local config file")?,
+                Err(_) => toml::Table::new(),
+            };
+            let sra = table
+                .entry("sra")
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or(eyre!("expected `sra` in {:?} to be a table", path))?;
+            // the decay is part of the weights since FSRS-6
+            sra.remove("decay");
+            // go through the decimal representation to avoid f32 -> f64 noise in the file
+            let weights = weights
+                .iter()
+                .map(|w| w.to_string().parse::<f64>().map(toml::Value::from))
+                .collect::<std::result::Result<Vec<toml::Value>, _>>()?;
+            sra.insert("w".into(), toml::Value::Array(weights));
+            std::fs::write(&path, toml::to_string(&table)?)
+                .wrap_err("writing local config file")?;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn desired_retention_overrides() {
+            let config: SchedulingConfig = toml::from_str(
+                r#"
+                desired_retention = 0.85
+                [models.reading]
+                desired_retention = 0.7
+                [tags.exam]
+                desired_retention = 0.95
+                [tags.hobby]
+                desired_retention = 0.8
+                "#,
+            )
+            .unwrap();
+            assert!(config.validate().is_ok());
+            assert_eq!(config.desired_retention("flashcard", &[]), 0.85);
+            assert_eq!(config.desired_retention("flashcard", &["hobby"]), 0.8);
+            assert_eq!(
+                config.desired_retention("flashcard", &["hobby", "exam"]),
+                0.95
+            );
+            assert_eq!(config.desired_retention("reading", &["exam"]), 0.7);
+
+            let config: SchedulingConfig = toml::from_str("desired_retention = 1.0").unwrap();
+            assert!(config.validate().is_err());
+        }
+
+        #[test]
+        fn scheduler_overrides() {
+            let config: SchedulingConfig = toml::from_str(
+                r#"
+                [models.reading]
+                scheduler = "leitner"
+                [tags.vocab]
+                scheduler = "sm2"
+                "#,
+            )
+            .unwrap();
+            assert!(config.validate().is_ok());
+            assert_eq!(config.scheduler("flashcard", &[]), SchedulerKind::Fsrs);
+            assert_eq!(
+                config.scheduler("flashcard", &["vocab"]),
+                SchedulerKind::Sm2
+            );
+            assert_eq!(
+                config.scheduler("reading", &["vocab"]),
+                SchedulerKind::Leitner
+            );
+
+            let config: SchedulingConfig = toml::from_str("leitner.intervals = []").unwrap();
+            assert!(config.validate().is_err());
+        }
+
+        #[test]
+        fn steps_overrides() {
+            let config: SchedulingConfig = toml::from_str(
+                r#"
+                learning_steps = ["5m"]
+                [models.reading]
+                learning_steps = []
+                relearning_steps = ["1h", "1d"]
+                "#,
+            )
+            .unwrap();
+            let steps = config.steps("flashcard", &[]);
+            assert_eq!(steps.learning, [Step(time::Duration::minutes(5))]);
+            assert_eq!(steps.relearning, [Step(time::Duration::minutes(10))]);
+            let steps = config.steps("reading", &[]);
+            assert!(steps.learning.is_empty());
+            assert_eq!(steps.relearning[1], Step(time::Duration::DAY));
+
+            assert!(toml::from_str::<SchedulingConfig>(r#"learning_steps = ["5"]"#).is_err());
+        }
+
+        #[test]
+        fn interval_bounds() {
+            let config: SchedulingConfig = toml::from_str("maximum_interval = 365").unwrap();
+            assert!(config.validate().is_ok());
+            assert_eq!(config.bounds().clamp(1000.0), 365.0);
+            assert_eq!(config.bounds().clamp(0.1), 1.0);
+
+            let config: SchedulingConfig = toml::from_str("minimum_interval = 400").unwrap();
+            assert!(config.validate().is_ok());
+            let config: SchedulingConfig =
+                toml::from_str("minimum_interval = 400\nmaximum_interval = 365").unwrap();
+            assert!(config.validate().is_err());
+            let config: SchedulingConfig = toml::from_str("maximum_interval = 1e12").unwrap();
+            assert!(config.validate().is_err());
+        }
+
+        #[test]
+        #[allow(clippy::result_large_err)] // figment::Jail returns figment::Error
+        fn fuzz_settings() {
+            figment::Jail::expect_with(|jail| {
+                let root = jail.directory().to_owned();
+                jail.set_env("SPBASED_SCHEDULING__FUZZ_SEED", 42);
+                let config = AppConfig::resolve(AppRoot { root: root.clone() }).unwrap();
+                assert_eq!(config.scheduling.fuzz_seed, 42);
+                let factor = config.scheduling.fuzz_factor(1, 0);
+                assert_eq!(factor, Some(sra::fuzz::factor(42, 1, 0)));
+
+                // turned off for this collection
+                jail.create_dir(".spbased")?;
+                jail.create_file(".spbased/config.toml", "scheduling.fuzz = false")?;
+                let config = AppConfig::resolve(AppRoot { root }).unwrap();
+                assert_eq!(config.scheduling.fuzz_factor(1, 0), None);
+                Ok(())
+            });
+        }
+
+        #[test]
+        fn daily_limits() {
+            let config: LimitsConfig = toml::from_str(
+                r#"
+                new_per_day = 20
+                [tags.exam]
+                reviews_per_day = 50
+                "#,
+            )
+            .unwrap();
+            let studied = Studied {
+                new: 25,
+                reviews: 30,
+            };
+            assert_eq!(config.all.remaining(&studied, true), Some(0));
+            assert_eq!(config.all.remaining(&studied, false), None);
+            assert_eq!(config.tags["exam"].remaining(&studied, false), Some(20));
+            assert_eq!(config.tags["exam"].remaining(&studied, true), None);
+        }
+
+        #[test]
+        #[allow(clippy::result_large_err)]
+        fn review_order() {
+            use crate::queries::review::Order;
+            figment::Jail::expect_with(|jail| {
+                let root = jail.directory().to_owned();
+                let config = AppConfig::resolve(AppRoot { root: root.clone() }).unwrap();
+                assert_eq!(config.review.due_order, Order::Retrievability);
+                assert_eq!(config.review.new_order, Order::Created);
+                assert_eq!(config.review.query_order, None);
+                assert!(config.review.bury_siblings);
+
+                jail.create_dir(".spbased")?;
+                jail.create_file(
+                    ".spbased/config.toml",
+                    "[review]\ndue_order = 'overdue'\nquery_order = 'created'",
+                )?;
+                let config = AppConfig::resolve(AppRoot { root }).unwrap();
+                assert_eq!(config.review.due_order, Order::Overdue);
+                assert_eq!(config.review.query_order, Some(Order::Created));
+                Ok(())
+            });
+        }
+
+        #[test]
+        #[allow(clippy::result_large_err)]
+        fn study_day() {
+            use time::macros::{date, datetime};
+            figment::Jail::expect_with(|jail| {
+                let root = jail.directory().to_owned();
+                let config = AppConfig::resolve(AppRoot { root: root.clone() }).unwrap();
+                assert_eq!(config.study_day, StudyDay::default());
+
+                jail.create_dir(".spbased")?;
+                jail.create_file(
+                    ".spbased/config.toml",
+                    "day_starts_at = 4\ntimezone = 'Asia/Tokyo'",
+                )?;
+                let config = AppConfig::resolve(AppRoot { root: root.clone() }).unwrap();
+                let day = config.settings("flashcard", &[]).day;
+                assert_eq!(day, config.study_day);
+                assert_eq!(
+                    day.date(datetime!(2024-01-01 18:00 UTC)),
+                    date!(2024 - 01 - 01)
+                );
+
+                jail.create_file(".spbased/config.toml", "timezone = 'Tokyo'")?;
+                assert!(AppConfig::resolve(AppRoot { root: root.clone() }).is_err());
+                jail.create_file(".spbased/config.toml", "day_starts_at = 24")?;
+                assert!(AppConfig::resolve(AppRoot { root }).is_err());
+                Ok(())
+            });
+        }
+
+        #[test]
+        fn new_placement() {
+            let due = || vec![1, 2, 3, 4, 5];
+            let new = || vec![10, 20];
+            let mixed = NewPlacement::Mixed;
+            assert_eq!(mixed.interleave(2, due(), new()), [1, 2, 10, 3, 4, 20, 5]);
+            assert_eq!(mixed.interleave(5, due(), new()), [1, 2, 3, 4, 5, 10, 20]);
+            assert_eq!(mixed.interleave(0, due(), new()), [1, 10, 2, 20, 3, 4, 5]);
+            assert_eq!(mixed.interleave(2, vec![], new()), [10, 20]);
+            let before = NewPlacement::Before.interleave(2, due(), new());
+            assert_eq!(before, [10, 20, 1, 2, 3, 4, 5]);
+            let after = NewPlacement::After.interleave(2, due(), new());
+            assert_eq!(after, [1, 2, 3, 4, 5, 10, 20]);
+        }
+
+        #[test]
+        fn leech_lapses() {
+            let leech = LeechConfig::default();
+            let handled: Vec<u32> = (1..=16).filter(|l| leech.handle_lapse(*l)).collect();
+            assert_eq!(handled, [8, 12, 16]);
+            assert!(!leech.is_leech(7) && leech.is_leech(9));
+            let never = LeechConfig {
+                threshold: 0,
+                ..Default::default()
+            };
+            assert!(!never.is_leech(100) && !never.handle_lapse(100));
+        }
+
+        #[test]
+        #[allow(clippy::result_large_err)]
+        fn easy_days() {
+            figment::Jail::expect_with(|jail| {
+                let root = jail.directory().to_owned();
+                jail.create_dir(".spbased")?;
+                jail.create_file(
+                    ".spbased/config.toml",
+                    "[scheduling.easy_days]\nsaturday = 0.5\nSunday = 0",
+                )?;
+                let config = AppConfig::resolve(AppRoot { root: root.clone() }).unwrap();
+                let settings = config.scheduling.settings("flashcard", &[]);
+                assert_eq!(settings.easy_days, [1.0, 1.0, 1.0, 1.0, 1.0, 0.5, 0.0]);
+
+                for day in ["someday", "\"\"", "\"ñ\""] {
+                    let file = format!("scheduling.easy_days.{day} = 0.5");
+                    jail.create_file(".spbased/config.toml", &file)?;
+                    assert!(AppConfig::resolve(AppRoot { root: root.clone() }).is_err());
+                }
+                jail.create_file(".spbased/config.toml", "scheduling.easy_days.monday = 2")?;
+                assert!(AppConfig::resolve(AppRoot { root }).is_err());
+                Ok(())
+            });
+        }
     }
 }
 
@@ -165,7 +847,7 @@ pub mod command {
     pub fn init(directory: PathBuf, force: bool) -> Result<()> {
         let full_path: PathBuf = directory.try_resolve()?.into_owned().normalize();
         let spbased_dir = config::app_work_dir(&full_path);
-        log::info!("initializing spbased dir {:?}", &spbased_dir);
+        log::info!("initializing spbased dir {:?}", spbased_dir);
 
         // confirm that user wants to overwrite dir
         if spbased_dir.exists() {
@@ -181,7 +863,11 @@ pub mod command {
         std::fs::create_dir_all(&spbased_dir)?;
 
         // init the db
-        _ = db::DB::open(&spbased_dir.join(DB_NAME))?;
+        _ = db::DB::open(
+            spbased_dir.join(DB_NAME),
+            &sra::Parameters::default(),
+            Default::default(),
+        )?;
 
         Ok(())
     }
@@ -192,16 +878,28 @@ pub mod command {
 
         use super::*;
 
-        pub fn handle_command(mut c: DB, command: ItemCommand) -> Result<Option<String>> {
+        pub fn handle_command(
+            mut c: DB,
+            config: &config::AppConfig,
+            command: ItemCommand,
+        ) -> Result<Option<String>> {
             Ok(match command {
-                ItemCommand::Add { model, data, tags } => {
+                ItemCommand::Add {
+                    model,
+                    data,
+                    tags,
+                    group,
+                } => {
                     let id = queries::item::add(
                         &mut c,
                         &model,
                         &data.to_string(),
                         &(tags.iter().map(|s| s.as_str()).collect::<Vec<&str>>()),
                     )?;
-                    Some(format!("{}", json!({ "id": id }).to_string()))
+                    if group.is_some() {
+                        queries::item::set_sibling_group(&mut c, id, group.as_deref())?;
+                    }
+                    Some(format!("{}", json!({ "id": id })))
                 }
                 ItemCommand::Edit {
                     id,
@@ -209,21 +907,26 @@ pub mod command {
                     data,
                     add_tags,
                     remove_tags,
+                    group,
                 } => {
                     if let Some(model) = model {
                         queries::item::edit_model(&mut c, id, &model)?;
                     }
+                    if let Some(group) = group {
+                        let group = Some(group.as_str()).filter(|g| !g.is_empty());
+                        queries::item::set_sibling_group(&mut c, id, group)?;
+                    }
                     if let Some(data) = data {
                         queries::item::edit_data(&mut c, id, &data.to_string())?;
                     }
-                    if add_tags.len() > 0 {
+                    if !add_tags.is_empty() {
                         queries::item::add_tags(
-                            &mut c,
+                            &c,
                             id,
                             &(add_tags.iter().map(|s| s.as_str()).collect::<Vec<&str>>()),
                         )?;
                     }
-                    if remove_tags.len() > 0 {
+                    if !remove_tags.is_empty() {
                         queries::item::remove_tags(
                             &mut c,
                             id,
@@ -246,7 +949,7 @@ pub mod command {
                 } => {
                     let tags = queries::item::get_tags(&mut c, id)?;
                     let tags = jmessearch_and_prettify(tags, post_filter, pretty)?;
-                    Some(format!("{tags}"))
+                    Some(tags.to_string())
                 }
                 ItemCommand::Query {
                     pre_filter,
@@ -254,6 +957,7 @@ pub mod command {
                     include_tags,
                     exclude_tags,
                     pretty,
+                    order,
                 } => {
                     // we apply sql filtering on items
                     let items = queries::item::query(
@@ -267,16 +971,85 @@ pub mod command {
                             .iter()
                             .map(|s| s.as_str())
                             .collect::<Vec<&str>>()),
+                        order.or(config.review.query_order),
                     )?;
                     // we apply json filter on items
                     let items = jmessearch_and_prettify(items, post_filter, pretty)?;
                     // TODO check README TODO for what to do here
-                    Some(format!("{items}"))
+                    Some(items.to_string())
+                }
+                ItemCommand::Suspend(items) => {
+                    let ids = selected(&mut c, items)?;
+                    for id in &ids {
+                        queries::item::set_suspended(&c, *id, true)?;
+                    }
+                    Some(json!({ "n_items": ids.len() }).to_string())
+                }
+                ItemCommand::Unsuspend(items) => {
+                    let ids = selected(&mut c, items)?;
+                    for id in &ids {
+                        queries::item::set_suspended(&c, *id, false)?;
+                    }
+                    Some(json!({ "n_items": ids.len() }).to_string())
+                }
+                ItemCommand::Bury { items, days } => {
+                    let day = &config.study_day;
+                    let today = day.date(OffsetDateTime::now_utc());
+                    let until = day.start(today + time::Duration::days(days as i64));
+                    let ids = selected(&mut c, items)?;
+                    for id in &ids {
+                        queries::item::set_buried_until(&mut c, *id, Some(until))?;
+                    }
+                    let until = until.format(&time::format_description::well_known::Rfc3339)?;
+                    Some(json!({ "n_items": ids.len(), "buried_until": until }).to_string())
+                }
+                ItemCommand::Unbury(items) => {
+                    let ids = selected(&mut c, items)?;
+                    for id in &ids {
+                        queries::item::set_buried_until(&mut c, *id, None)?;
+                    }
+                    Some(json!({ "n_items": ids.len() }).to_string())
+                }
+                ItemCommand::Leeches {
+                    pre_filter,
+                    post_filter,
+                    pretty,
+                } => {
+                    use filter_language::{AstNode, Operator::*};
+                    let threshold = config.leech.threshold;
+                    let items = match threshold {
+                        0 => vec![],
+                        _ => {
+                            let leech = AstNode::comparative_filter(
+                                "n_lapses",
+                                Geq,
+                                AstNode::integer(threshold as i32),
+                            );
+                            let filter = match pre_filter {
+                                Some(f) => AstNode::logical_filter(f, And, leech),
+                                None => leech,
+                            };
+                            let order = queries::review::Order::Created;
+                            queries::item::query(&mut c, Some(filter), &[], &[], Some(order))?
+                        }
+                    };
+                    Some(jmessearch_and_prettify(items, post_filter, pretty)?)
                 }
             })
         }
     }
 
+    /// The ids of the selected items
+    fn selected(c: &mut DB, items: ItemSelection) -> Result<Vec<i32>> {
+        Ok(match items.id {
+            Some(id) => vec![queries::item::get(c, id)?.id],
+            None => queries::item::query(c, items.pre_filter, &[], &[], None)?
+                .iter()
+                .map(|item| item.id)
+                .collect(),
+        })
+    }
+
     fn jmessearch_and_prettify<T: serde::ser::Serialize>(
         value: T,
         filter: Option<String>,
@@ -300,118 +1073,692 @@ pub mod command {
     }
 
     pub mod review {
-        use model::Maturity;
-        use rand::Rng;
-        use time::Duration;
+        use crate::config::{AppConfig, NewPlacement};
+        use crate::filter_language::AstNode;
+        use serde::Serialize;
+        use std::collections::BTreeMap;
 
         use super::*;
         // use serde_json::json;
 
-        pub fn handle_command(mut c: DB, command: ReviewCommand) -> Result<Option<String>> {
+        /// The outcome of a review with a certain grade
+        #[derive(Serialize, Debug)]
+        struct Preview {
+            maturity: model::Maturity,
+            stability: sra::model::Stability,
+            difficulty: sra::model::Difficulty,
+            /// days until the item is due
+            interval: sra::model::Interval,
+            #[serde(with = "time::serde::rfc3339")]
+            due_date: OffsetDateTime,
+        }
+
+        impl Preview {
+            fn new(next: &sra::CardState, due: OffsetDateTime, now: OffsetDateTime) -> Self {
+                Self {
+                    maturity: next.maturity.into(),
+                    stability: next.s,
+                    difficulty: next.d,
+                    interval: (due - now).as_seconds_f32() / time::Duration::DAY.as_seconds_f32(),
+                    due_date: due,
+                }
+            }
+        }
+
+        /// The scheduler and settings configured for the model and tags of `item`
+        pub(super) fn scheduling_of(
+            c: &mut DB,
+            config: &AppConfig,
+            item: &model::Item,
+        ) -> Result<(Box<dyn sra::Scheduler>, sra::scheduler::Settings)> {
+            let tags = queries::item::get_tags(c, item.id)?;
+            let tags: Vec<&str> = tags.iter().map(|t| t.name.as_str()).collect();
+            Ok((
+                config.scheduler(&item.model, &tags),
+                config.settings(&item.model, &tags),
+            ))
+        }
+
+        /// Tag and/or suspend an item that just became a leech
+        /// Returns the leech tag if the item did not have it yet
+        fn handle_leech<'a>(
+            c: &Connection,
+            config: &'a AppConfig,
+            id: i32,
+            lapses: u32,
+        ) -> Result<Option<&'a str>> {
+            use crate::config::LeechAction::*;
+            let leech = &config.leech;
+            log::info!("item {id} is a leech, it lapsed {lapses} times");
+            let mut tagged = None;
+            if matches!(leech.action, Tag | Both)
+                && queries::item::add_tags(c, id, &[&leech.tag])? > 0
+            {
+                tagged = Some(leech.tag.as_str());
+            }
+            if matches!(leech.action, Suspend | Both) {
+                queries::item::set_suspended(c, id, true)?;
+            }
+            Ok(tagged)
+        }
+
+        /// The number of items due on each day from `now` on, if load balancing is on
+        pub(super) fn load_of(
+            c: &mut DB,
+            config: &AppConfig,
+            now: OffsetDateTime,
+        ) -> Result<Option<impl Fn(time::Date) -> u32>> {
+            if !config.scheduling.load_balance {
+                return Ok(None);
+            }
+            let counts = queries::review::n_due_per_day(c, config.study_day.date(now))?;
+            Ok(Some(move |date| counts.get(&date).copied().unwrap_or(0)))
+        }
+
+        /// What remains today of the daily limits of new items or reviews
+        struct Quota {
+            /// of the global limit, `None` if there is none
+            remaining: Option<u32>,
+            /// of the limits of tags
+            tags: BTreeMap<model::TagName, u32>,
+        }
+
+        impl Quota {
+            /// The quota of new items (`new`) or reviews
+            fn of(c: &mut DB, config: &AppConfig, new: bool) -> Result<Self> {
+                let day = &config.study_day;
+                let today = day.start(day.date(OffsetDateTime::now_utc()));
+                let limits = &config.limits;
+                let studied = queries::review::n_studied(c, today, None)?;
+                let mut tags = BTreeMap::new();
+                for (tag, limit) in &limits.tags {
+                    let studied = queries::review::n_studied(c, today, Some(tag))?;
+                    if let Some(remaining) = limit.remaining(&studied, new) {
+                        tags.insert(tag.clone(), remaining);
+                    }
+                }
+                Ok(Self {
+                    remaining: limits.all.remaining(&studied, new),
+                    tags,
+                })
+            }
+
+            /// The items that may not be studied at all
+            fn capped(&self) -> queries::review::Capped {
+                queries::review::Capped {
+                    all: self.remaining == Some(0),
+                    tags: self
+                        .tags
+                        .iter()
+                        .filter(|(_, n)| **n == 0)
+                        .map(|(tag, _)| tag.clone())
+                        .collect(),
+                }
+            }
+
+            /// The `items` that fit within the quota, in order, using up the quota
+            fn take(&mut self, c: &mut DB, items: Vec<model::Item>) -> Result<Vec<model::Item>> {
+                let mut taken = vec![];
+                for item in items {
+                    if self.remaining == Some(0) {
+                        break;
+                    }
+                    let tags = match self.tags.is_empty() {
+                        true => vec![],
+                        false => queries::item::get_tags(c, item.id)?,
+                    };
+                    let limited: Vec<_> = tags
+                        .iter()
+                        .filter(|t| self.tags.contains_key(&t.name))
+                        .collect();
+                    if limited.iter().any(|t| self.tags[&t.name] == 0) {
+                        continue;
+                    }
+                    for tag in limited {
+                        *self.tags.get_mut(&tag.name).unwrap() -= 1;
+                    }
+                    if let Some(n) = &mut self.remaining {
+                        *n -= 1;
+                    }
+                    taken.push(item);
+                }
+                Ok(taken)
+            }
+        }
+
+        /// Up to `limit` items to study in order: (re)learning items first, followed by due
+        /// and new items placed by `placement`, within the daily limits
+        fn queue(
+            c: &mut DB,
+            config: &AppConfig,
+            limit: usize,
+            pre_filter: Option<AstNode>,
+            placement: NewPlacement,
+            every: u32,
+        ) -> Result<Vec<model::Item>> {
+            use model::Maturity::*;
+            let review = &config.review;
+            let mut reviews = Quota::of(c, config, false)?;
+            let mut new = Quota::of(c, config, true)?;
+            let (learning, due): (Vec<_>, Vec<_>) = queries::review::due_items(
+                c,
+                pre_filter.clone(),
+                review.due_order,
+                &reviews.capped(),
+                limit,
+            )?
+            .into_iter()
+            .partition(|item| matches!(item.maturity, Learning | Relearning));
+            let due = reviews.take(c, due)?;
+            let new_items =
+                queries::review::new_items(c, pre_filter, review.new_order, &new.capped(), limit)?;
+            let new_items = new.take(c, new_items)?;
+
+            // (re)learning items come first, as their steps are only minutes apart
+            let mut items = learning;
+            items.extend(placement.interleave(every, due, new_items));
+            items.truncate(limit);
+            Ok(items)
+        }
+
+        /// The id of the open study session
+        fn open_session(c: &mut DB) -> Result<i32> {
+            queries::session::open(c)?.ok_or(eyre!(
+                "there is no open session, start one with `review session start`"
+            ))
+        }
+
+        pub fn handle_command(
+            mut c: DB,
+            config: &AppConfig,
+            command: ReviewCommand,
+        ) -> Result<Option<String>> {
             let res: Option<String> = match command {
                 ReviewCommand::Next(cmd) => match cmd {
                     NextReviewCommand::New {
                         pre_filter,
                         post_filter,
                         pretty,
+                        order,
                     } => {
+                        let order = order.unwrap_or(config.review.new_order);
+                        let capped = Quota::of(&mut c, config, true)?.capped();
                         // we apply sql filtering on items
-                        let items = queries::review::study_new(&mut c, pre_filter)?;
+                        let items = queries::review::study_new(&mut c, pre_filter, order, &capped)?;
 
                         // we apply json filter on items
                         let items = jmessearch_and_prettify(items, post_filter, pretty)?;
-                        Some(format!("{}", items))
+                        Some(items.to_string())
                     }
                     NextReviewCommand::Due {
                         pre_filter,
                         post_filter,
                         pretty,
+                        order,
                     } => {
+                        let order = order.unwrap_or(config.review.due_order);
+                        let capped = Quota::of(&mut c, config, false)?.capped();
                         // we apply sql filtering on items
-                        let items = queries::review::study_due(&mut c, pre_filter)?;
+                        let items = queries::review::study_due(&mut c, pre_filter, order, &capped)?;
 
                         // we apply json filter on items
                         let items = jmessearch_and_prettify(items, post_filter, pretty)?;
-                        Some(format!("{items}"))
+                        Some(items.to_string())
                     }
                 },
-                ReviewCommand::Score { id, grade } => {
-                    use sra::model::Grade::*;
-                    use Maturity::*;
-
+                ReviewCommand::Queue {
+                    limit,
+                    pre_filter,
+                    post_filter,
+                    pretty,
+                    new_placement,
+                    new_every,
+                } => {
+                    let placement = new_placement.unwrap_or(config.review.new_placement);
+                    let every = new_every.unwrap_or(config.review.new_every);
+                    let items = queue(&mut c, config, limit, pre_filter, placement, every)?;
+                    Some(jmessearch_and_prettify(items, post_filter, pretty)?)
+                }
+                ReviewCommand::Session(cmd) => match cmd {
+                    SessionCommand::Start {
+                        limit,
+                        pre_filter,
+                        new_placement,
+                        new_every,
+                    } => {
+                        if let Some(id) = queries::session::open(&c)? {
+                            return Err(eyre!(
+                                "session {id} is still open, end it with `review session end`"
+                            ));
+                        }
+                        let placement = new_placement.unwrap_or(config.review.new_placement);
+                        let every = new_every.unwrap_or(config.review.new_every);
+                        let items = queue(&mut c, config, limit, pre_filter, placement, every)?;
+                        let ids: Vec<i32> = items.iter().map(|item| item.id).collect();
+                        let id = queries::session::start(&mut c, &ids)?;
+                        log::info!("started session {id} with {} items", ids.len());
+                        Some(json!({ "id": id, "n_items": ids.len() }).to_string())
+                    }
+                    SessionCommand::Next {
+                        post_filter,
+                        pretty,
+                    } => {
+                        let session = open_session(&mut c)?;
+                        let item = match queries::session::next(&mut c, session)? {
+                            Some(id) => Some(queries::item::get(&mut c, id)?),
+                            None => None,
+                        };
+                        Some(jmessearch_and_prettify(item, post_filter, pretty)?)
+                    }
+                    SessionCommand::End {
+                        post_filter,
+                        pretty,
+                    } => {
+                        let session = open_session(&mut c)?;
+                        queries::session::end(&mut c, session, OffsetDateTime::now_utc())?;
+                        let summary = queries::session::summary(&mut c, session)?;
+                        Some(jmessearch_and_prettify(summary, post_filter, pretty)?)
+                    }
+                },
+                ReviewCommand::Score {
+                    id,
+                    grade,
+                    at,
+                    duration_ms,
+                } => {
                     let item = queries::item::get(&mut c, id)?;
                     let id = item.id;
+                    let state = item.card_state();
 
-                    let today = time::OffsetDateTime::now_utc();
-                    let duration_since_last_review = today - item.last_review_date;
-                    let n_days_since_last_review = duration_since_last_review.as_seconds_f32()
-                        / Duration::DAY.as_seconds_f32();
-                    let last_review_was_today = Duration::DAY < duration_since_last_review;
+                    let now = time::OffsetDateTime::now_utc();
+                    let today = at.unwrap_or(now);
+                    if today > now {
+                        return Err(eyre!("the review time {today} lies in the future"));
+                    }
+                    if today < item.last_review_date {
+                        return Err(eyre!(
+                            "item {id} was last reviewed at {}, after the given review time",
+                            item.last_review_date
+                        ));
+                    }
+                    let (scheduler, settings) = scheduling_of(&mut c, config, &item)?;
+                    let fuzz = config.scheduling.fuzz_factor(id, state.reps);
+                    let load = load_of(&mut c, config, today)?;
+                    let (next, due) = sra::schedule(
+                        &*scheduler,
+                        &settings,
+                        &state,
+                        grade,
+                        today,
+                        fuzz,
+                        load.as_ref().map(|l| l as &dyn Fn(time::Date) -> u32),
+                    );
 
-                    match (item.maturity, grade, last_review_was_today) {
-                        // we need to review the item again in this session
-                        (New, Again | Hard, _) => {
-                            queries::review::increment_n_reviews(&mut c, id)?;
-                        }
-                        // promote item from new to young
-                        (New, g, _) => {
-                            let s = sra::init::s(g);
-                            let d = sra::init::d(g);
-                            queries::review::set_maturity(&mut c, id, Young)?;
-                            queries::review::increment_n_reviews(&mut c, id)?;
-                            queries::review::set_sra_params(&mut c, id, s, d, today)?;
-                        }
-                        (Young | Tenured, Again, true) => {
-                            // the item was already reviewed today, but somehow we are reviewing it again
-                            // with a failing grade. This could be because the user is cramming review items
-                        }
-                        (Young | Tenured, Again, false) => {
-                            let r = sra::r(n_days_since_last_review, item.stability);
-                            let s = sra::update::fail::s(item.stability, item.difficulty, r);
-                            let d = sra::update::d(item.difficulty, Again);
-                            queries::review::increment_n_reviews(&mut c, id)?;
-                            queries::review::increment_n_lapses(&mut c, id)?;
-                            queries::review::set_sra_params(&mut c, id, s, d, today)?;
-                        }
-                        (Young | Tenured, g, true) => {
-                            let s = sra::update::shortterm::s(item.stability, g);
-                            let d = sra::update::d(item.difficulty, g);
-                            if s > 100.0 {
-                                queries::review::set_maturity(&mut c, id, Tenured)?;
-                            }
-                            queries::review::increment_n_reviews(&mut c, id)?;
-                            queries::review::set_sra_params(&mut c, id, s, d, today)?;
-                        }
-                        (Young | Tenured, g, false) => {
-                            let r = sra::r(n_days_since_last_review, item.stability);
-                            let s = sra::update::success::s(item.stability, item.difficulty, r, g);
-                            let d = sra::update::d(item.difficulty, g);
-                            if s > 100.0 {
-                                queries::review::set_maturity(&mut c, id, Tenured)?;
-                            }
-                            let s = s * (1.0 + rand::rng().random_range(-0.1..=0.1)); // add some random noise on ordinary reviews
-                            queries::review::increment_n_reviews(&mut c, id)?;
-                            queries::review::set_sra_params(&mut c, id, s, d, today)?;
-                        }
-                    };
+                    // the item, its log entry and its session are updated together, so a
+                    // failure leaves no review behind that `review undo` would miss
+                    let tx = c.transaction()?;
+                    queries::review::set_card_state(&tx, id, &next, due)?;
+                    let mut leech_tag = None;
+                    if next.lapses > state.lapses && config.leech.handle_lapse(next.lapses) {
+                        leech_tag = handle_leech(&tx, config, id, next.lapses)?;
+                    }
+                    if let (true, Some(group)) = (config.review.bury_siblings, &item.sibling_group)
+                    {
+                        // siblings are seen again from tomorrow on
+                        let tomorrow = config.study_day.next(today);
+                        queries::item::bury_siblings(&tx, id, group, tomorrow)?;
+                    }
+                    let review = queries::review_log::add(
+                        &tx,
+                        grade,
+                        today,
+                        state.elapsed_days(today),
+                        &item,
+                        sra::model::ModelData {
+                            s: next.s,
+                            d: next.d,
+                        },
+                        duration_ms,
+                    )?;
+                    if let Some(tag) = leech_tag {
+                        // undoing the review takes the tag away again
+                        queries::review_log::set_leech_tag(&tx, review, tag)?;
+                    }
+                    if let Some(session) = queries::session::open(&tx)? {
+                        // failed items are shown again later in the session
+                        let again = (grade == sra::model::Grade::Again)
+                            .then_some(config.review.again_offset);
+                        queries::session::record(&tx, session, id, review, again)?;
+                    }
+                    tx.commit()?;
                     None
                 }
+                ReviewCommand::Undo { id } => {
+                    let id = queries::review_log::undo(&mut c, id)?
+                        .ok_or(eyre!("there is no review to undo"))?;
+                    log::info!("undid the last review of item {id}");
+                    Some(json!({ "id": id }).to_string())
+                }
+                ReviewCommand::Preview {
+                    id,
+                    post_filter,
+                    pretty,
+                } => {
+                    use sra::model::Grade::*;
+                    let item = queries::item::get(&mut c, id)?;
+                    let state = item.card_state();
+                    let now = time::OffsetDateTime::now_utc();
+                    let (scheduler, settings) = scheduling_of(&mut c, config, &item)?;
+                    let fuzz = config.scheduling.fuzz_factor(item.id, state.reps);
+                    let load = load_of(&mut c, config, now)?;
+                    let due_on = load.as_ref().map(|l| l as &dyn Fn(time::Date) -> u32);
+                    let previews: BTreeMap<sra::model::Grade, Preview> = [Again, Hard, Good, Easy]
+                        .into_iter()
+                        .map(|g| {
+                            let (next, due) =
+                                sra::schedule(&*scheduler, &settings, &state, g, now, fuzz, due_on);
+                            (g, Preview::new(&next, due, now))
+                        })
+                        .collect();
+                    let previews = jmessearch_and_prettify(previews, post_filter, pretty)?;
+                    Some(previews.to_string())
+                }
+                ReviewCommand::Log {
+                    pre_filter,
+                    post_filter,
+                    pretty,
+                } => {
+                    let logs = queries::review_log::query(&mut c, pre_filter)?;
+                    let logs = jmessearch_and_prettify(logs, post_filter, pretty)?;
+                    Some(logs.to_string())
+                }
                 ReviewCommand::QueryCount(cmd) => {
-                    let res = match cmd {
-                        QueryCountCommand::Due { filter } => {
-                            queries::review::query_n_due(&mut c, filter)?
-                        }
-                        QueryCountCommand::New { filter } => {
-                            queries::review::query_n_new(&mut c, filter)?
-                        }
+                    let (count, quota) = match cmd {
+                        QueryCountCommand::Due { filter } => (
+                            queries::review::query_n_due(&mut c, filter)?,
+                            Quota::of(&mut c, config, false)?,
+                        ),
+                        QueryCountCommand::New { filter } => (
+                            queries::review::query_n_new(&mut c, filter)?,
+                            Quota::of(&mut c, config, true)?,
+                        ),
                     };
-                    Some(format!("{res}"))
+                    Some(json!({ "count": count, "remaining": quota.remaining }).to_string())
                 }
             };
             Ok(res)
         }
     }
 
+    /// handlers for `spbasedctl sra`
+    pub mod algorithm {
+        use super::*;
+        use crate::config::AppConfig;
+        use sra::optimizer::{History, Optimizer, Review};
+        use sra::CardState;
+        use std::collections::BTreeMap;
+
+        pub fn handle_command(
+            mut c: DB,
+            config: &AppConfig,
+            command: SraCommand,
+        ) -> Result<Option<String>> {
+            Ok(match command {
+                SraCommand::Optimize {
+                    pre_filter,
+                    epochs,
+                    save,
+                    post_filter,
+                    pretty,
+                } => {
+                    let logs = queries::review_log::query(&mut c, pre_filter)?;
+                    // the logs are ordered by review date, group them per item
+                    let mut histories: BTreeMap<i32, History> = BTreeMap::new();
+                    let mut last_reviews: BTreeMap<i32, OffsetDateTime> = BTreeMap::new();
+                    let day = &config.study_day;
+                    for log in logs {
+                        // the same study day as in `review score`
+                        let last = last_reviews.insert(log.item_id, log.review_date);
+                        let same_day =
+                            last.is_some_and(|t| day.date(t) == day.date(log.review_date));
+                        histories.entry(log.item_id).or_default().push(Review {
+                            grade: log.grade,
+                            elapsed_days: log.elapsed_days,
+                            same_day,
+                        });
+                    }
+                    let histories: Vec<History> = histories.into_values().collect();
+
+                    let optimizer = Optimizer {
+                        epochs,
+                        ..Default::default()
+                    };
+                    let fit = optimizer.fit(config.sra, &histories);
+                    log::info!(
+                        "fitted weights on {} reviews, loss {} -> {}",
+                        fit.n_reviews,
+                        fit.loss_before,
+                        fit.loss_after
+                    );
+                    if save {
+                        config.save_weights(&fit.w)?;
+                        log::info!("saved weights to {:?}", config.local_config_path());
+                    }
+                    Some(jmessearch_and_prettify(fit, post_filter, pretty)?)
+                }
+                SraCommand::Reschedule {
+                    pre_filter,
+                    replay,
+                    dry_run,
+                } => {
+                    let items = queries::item::query(&mut c, pre_filter, &[], &[], None)?;
+                    let mut histories: BTreeMap<i32, Vec<model::ReviewLog>> = BTreeMap::new();
+                    for log in queries::review_log::query(&mut c, None)? {
+                        histories.entry(log.item_id).or_default().push(log);
+                    }
+                    let load = review::load_of(&mut c, config, OffsetDateTime::now_utc())?;
+                    let day = &config.study_day;
+                    let (mut n_earlier, mut n_later) = (0, 0);
+                    let mut updates = Vec::new();
+                    for item in &items {
+                        let Some(old_due) = item.due_date else {
+                            continue;
+                        };
+                        let (scheduler, settings) = review::scheduling_of(&mut c, config, item)?;
+                        let state = item.card_state();
+                        let history = histories.get(&item.id).map_or(&[][..], Vec::as_slice);
+                        // the item does not count against moving it, it is no longer due then
+                        let others = |date| {
+                            let n = load.as_ref().map_or(0, |load| load(date));
+                            n.saturating_sub(u32::from(date == day.date(old_due)))
+                        };
+                        let due_on = load
+                            .is_some()
+                            .then_some(&others as &dyn Fn(time::Date) -> u32);
+                        // items reviewed before the log was kept can not be replayed
+                        let (state, due) = if replay && history.len() == state.reps as usize {
+                            let mut replayed = (CardState::new(item.created_at), old_due);
+                            for (i, log) in history.iter().enumerate() {
+                                let (state, _) = replayed;
+                                let fuzz = config.scheduling.fuzz_factor(item.id, state.reps);
+                                // only the due date of the last review is kept
+                                let last = i + 1 == history.len();
+                                replayed = sra::schedule(
+                                    &*scheduler,
+                                    &settings,
+                                    &state,
+                                    log.grade,
+                                    log.review_date,
+                                    fuzz,
+                                    due_on.filter(|_| last),
+                                );
+                            }
+                            replayed
+                        } else {
+                            // the fuzz of the last review
+                            let fuzz = config
+                                .scheduling
+                                .fuzz_factor(item.id, state.reps.saturating_sub(1));
+                            // the days before the last review are unknown without a log
+                            let elapsed = history.last().map_or(0.0, |log| log.elapsed_days);
+                            let due = sra::scheduler::reschedule(
+                                &*scheduler,
+                                &settings,
+                                &state,
+                                elapsed,
+                                fuzz,
+                                due_on,
+                            );
+                            (state, due.unwrap_or(old_due))
+                        };
+                        if day.date(due) < day.date(old_due) {
+                            n_earlier += 1;
+                        } else if day.date(due) > day.date(old_due) {
+                            n_later += 1;
+                        }
+                        updates.push((item.id, state, due));
+                    }
+                    if !dry_run {
+                        let tx = c.transaction()?;
+                        for (id, state, due) in &updates {
+                            queries::review::set_card_state(&tx, *id, state, *due)?;
+                        }
+                        tx.commit()?;
+                    }
+                    let n_rescheduled = n_earlier + n_later;
+                    let moved = if dry_run { "would move" } else { "moved" };
+                    log::info!("{n_rescheduled} of {} due dates {moved}", items.len());
+                    Some(
+                        json!({
+                            "n_items": items.len(),
+                            "n_rescheduled": n_rescheduled,
+                            "n_earlier": n_earlier,
+                            "n_later": n_later,
+                            "dry_run": dry_run,
+                        })
+                        .to_string(),
+                    )
+                }
+            })
+        }
+    }
+
+    pub mod stats {
+        use super::*;
+        use crate::config::AppConfig;
+        use serde::Serialize;
+        use sra::forecast::{Card, Grading, NewCards};
+
+        time::serde::format_description!(iso_date, Date, "[year]-[month]-[day]");
+
+        /// The reviews forecast for a day
+        #[derive(Serialize)]
+        struct Day {
+            #[serde(with = "iso_date")]
+            date: time::Date,
+            /// the (mean) number of reviews
+            reviews: f32,
+            /// the number of new items planned to be learnt
+            new: u32,
+        }
+
+        pub fn handle_command(
+            mut c: DB,
+            config: &AppConfig,
+            command: StatsCommand,
+        ) -> Result<Option<String>> {
+            Ok(match command {
+                StatsCommand::Forecast {
+                    days,
+                    new_per_day,
+                    new_model,
+                    simulate,
+                    seed,
+                    pre_filter,
+                    chart,
+                    post_filter,
+                    pretty,
+                } => {
+                    let now = OffsetDateTime::now_utc();
+                    let mut items = queries::item::query(&mut c, pre_filter, &[], &[], None)?;
+                    // new items are only learnt as planned
+                    items.retain(|item| item.maturity != model::Maturity::New && !item.suspended);
+                    let scheduling = items
+                        .iter()
+                        .map(|item| review::scheduling_of(&mut c, config, item))
+                        .collect::<Result<Vec<_>>>()?;
+                    let cards: Vec<Card> = items
+                        .iter()
+                        .zip(&scheduling)
+                        .map(|(item, (scheduler, settings))| Card {
+                            state: item.card_state(),
+                            // buried items are left out of reviews until they are unburied
+                            due: item
+                                .due_date
+                                .unwrap_or(now)
+                                .max(item.buried_until.unwrap_or(now)),
+                            scheduler: &**scheduler,
+                            settings,
+                        })
+                        .collect();
+
+                    let model = new_model.unwrap_or_default();
+                    let scheduler = config.scheduler(&model, &[]);
+                    let settings = config.settings(&model, &[]);
+                    let new = NewCards {
+                        per_day: new_per_day,
+                        scheduler: &*scheduler,
+                        settings: &settings,
+                    };
+                    let grading = match simulate {
+                        Some(runs) => Grading::Simulated {
+                            p: config.sra,
+                            runs,
+                            seed,
+                        },
+                        None => Grading::Good,
+                    };
+
+                    let day = &config.study_day;
+                    let reviews =
+                        sra::forecast::forecast(&cards, Some(&new), now, days, day, &grading);
+                    let forecast: Vec<Day> = reviews
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, reviews)| Day {
+                            date: day.date(now) + time::Duration::days(i as i64),
+                            reviews,
+                            new: new_per_day,
+                        })
+                        .collect();
+                    match chart {
+                        true => Some(bar_chart(&forecast)),
+                        false => Some(jmessearch_and_prettify(forecast, post_filter, pretty)?),
+                    }
+                }
+            })
+        }
+
+        /// One bar per day, scaled to the busiest day
+        fn bar_chart(days: &[Day]) -> String {
+            const WIDTH: f32 = 50.0;
+            let max = days.iter().map(|d| d.reviews).fold(1.0, f32::max);
+            days.iter()
+                .map(|d| {
+                    let bar = "#".repeat((d.reviews / max * WIDTH).round() as usize);
+                    format!(
+                        "{} {:.3} {bar:<w$} {:.1}",
+                        d.date,
+                        d.date.weekday(),
+                        d.reviews,
+                        w = WIDTH as usize
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+
     pub mod tag {
         use serde_json::json;
 
@@ -421,7 +1768,7 @@ pub mod command {
             Ok(match command {
                 TagCommand::Add { name } => {
                     let id = queries::tag::add(&mut c, &name)?;
-                    Some(format!("{}", json!({ "id": id }).to_string()))
+                    Some(format!("{}", json!({ "id": id })))
                 }
                 TagCommand::Edit { old_name, new_name } => {
                     queries::tag::edit(&mut c, &old_name, &new_name)?;
@@ -434,7 +1781,7 @@ pub mod command {
                 } => {
                     let tags = queries::tag::query(&mut c, pre_filter)?;
                     let tags = jmessearch_and_prettify(tags, post_filter, pretty)?;
-                    Some(format!("{tags}"))
+                    Some(tags.to_string())
                 }
             })
         }
@@ -448,7 +1795,6 @@ pub mod db {
     use rusqlite::Connection;
     use sql_minifier::macros::load_sql;
     use std::{
-        cell::LazyCell,
         ops::{Deref, DerefMut},
         path::Path,
     };
@@ -458,19 +1804,42 @@ pub mod db {
     pub const DB_OPEN: &str = load_sql!("sql/db_open.sql");
     pub const DB_CLOSE: &str = load_sql!("sql/db_close.sql");
 
-    pub const MIGRATIONS: LazyCell<Migrations> =
-        LazyCell::new(|| Migrations::new(vec![M::up(load_sql!("sql/001_init.sql"))]));
+    pub static MIGRATIONS: LazyLock<Migrations> = LazyLock::new(|| {
+        Migrations::new(vec![
+            M::up(load_sql!("sql/001_init.sql")),
+            M::up(load_sql!("sql/002_review_log.sql")),
+            M::up(load_sql!("sql/003_due_date.sql")),
+            M::up(load_sql!("sql/004_learning_steps.sql")),
+            M::up(load_sql!("sql/005_retrievability_order.sql")),
+            M::up(load_sql!("sql/006_suspended.sql")),
+            M::up(load_sql!("sql/007_buried.sql")),
+            M::up(load_sql!("sql/008_sibling_group.sql")),
+            M::up(load_sql!("sql/009_review_undo.sql")),
+            M::up(load_sql!("sql/010_review_duration.sql")),
+            M::up(load_sql!("sql/011_study_day.sql")),
+            M::up(load_sql!("sql/012_session.sql")),
+            M::up(load_sql!("sql/013_leech_undo.sql")),
+        ])
+    });
 
     #[repr(transparent)]
     pub struct DB(Connection);
 
     impl DB {
-        pub fn open<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<DB> {
+        /// Open the db at `path`. The sra functions evaluate the memory model with `p`, and
+        /// dates are study dates of `day`.
+        pub fn open<P: AsRef<Path> + std::fmt::Debug>(
+            path: P,
+            p: &sra::Parameters,
+            day: sra::day::StudyDay,
+        ) -> Result<DB> {
             log::debug!("opening db at {:?}", path);
             // open and create a sqlite db
             let mut conn = Connection::open(path).wrap_err("trying to open connection")?;
 
             conn.execute_batch(DB_OPEN)?;
+            // registered before the migrations, since the views depend on them
+            register_functions(&conn, *p, day).wrap_err("trying to register sql functions")?;
 
             MIGRATIONS.to_latest(&mut conn)?;
 
@@ -478,6 +1847,78 @@ pub mod db {
         }
     }
 
+    /// Register the sra functions, available to every query (and filter) on the db:
+    ///
+    /// - `retrievability(stability, last_review_date)`, the probability of recall right now
+    /// - `elapsed_days(last_review_date)`, the number of days since the last review
+    /// - `due_date(stability, last_review_date[, desired_retention])`, the moment the
+    ///   retrievability drops to the desired retention, 0.9 if left out
+    ///
+    /// They return null for items that have not been reviewed yet, whose stability is 0,
+    /// and `due_date` errors on a desired retention outside of (0, 1).
+    /// Besides them, `study_date([date])` is the study day that a date falls on, today if
+    /// left out.
+    fn register_functions(
+        conn: &Connection,
+        p: sra::Parameters,
+        day: sra::day::StudyDay,
+    ) -> rusqlite::Result<()> {
+        use rusqlite::functions::{Context, FunctionFlags};
+        // not deterministic, as they depend on the current time
+        let flags = FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_INNOCUOUS;
+
+        fn elapsed_days(last_review: OffsetDateTime) -> sra::model::Time {
+            let elapsed = OffsetDateTime::now_utc() - last_review;
+            elapsed.as_seconds_f32() / time::Duration::DAY.as_seconds_f32()
+        }
+        // the stability and last review date of a reviewed item
+        fn memory(ctx: &Context) -> rusqlite::Result<Option<(f32, OffsetDateTime)>> {
+            let s: Option<f64> = ctx.get(0)?;
+            let last_review: Option<OffsetDateTime> = ctx.get(1)?;
+            Ok(s.filter(|s| *s > 0.0).map(|s| s as f32).zip(last_review))
+        }
+        let due_date = move |ctx: &Context| {
+            let r: f64 = match ctx.len() {
+                3 => ctx.get(2)?,
+                _ => 0.9,
+            };
+            if !(r > 0.0 && r < 1.0) {
+                return Err(rusqlite::Error::UserFunctionError(
+                    format!("the desired retention must lie in (0, 1), got {r}").into(),
+                ));
+            }
+            // null if the due date lies beyond what a date can hold
+            Ok(memory(ctx)?.and_then(|(s, last_review)| {
+                let interval = sra::i(&p, r as f32, s);
+                time::Duration::checked_seconds_f32(interval * 86400.0)
+                    .and_then(|d| last_review.checked_add(d))
+            }))
+        };
+
+        conn.create_scalar_function("retrievability", 2, flags, move |ctx| {
+            Ok(
+                memory(ctx)?
+                    .map(|(s, last_review)| sra::r(&p, elapsed_days(last_review), s) as f64),
+            )
+        })?;
+        conn.create_scalar_function("elapsed_days", 1, flags, |ctx| {
+            let last_review: Option<OffsetDateTime> = ctx.get(0)?;
+            Ok(last_review.map(|t| elapsed_days(t) as f64))
+        })?;
+        conn.create_scalar_function("due_date", 2, flags, due_date)?;
+        conn.create_scalar_function("due_date", 3, flags, due_date)?;
+        let study_date = move |ctx: &Context| {
+            let t: Option<OffsetDateTime> = match ctx.len() {
+                1 => ctx.get(0)?,
+                _ => Some(OffsetDateTime::now_utc()),
+            };
+            Ok(t.map(|t| day.date(t)))
+        };
+        conn.create_scalar_function("study_date", 0, flags, study_date)?;
+        conn.create_scalar_function("study_date", 1, flags, study_date)?;
+        Ok(())
+    }
+
     // util traits
     impl Drop for DB {
         fn drop(&mut self) {
@@ -31,7 +31,7 @@ pub mod item {
             .prepare(minify_sql!(
                 "insert into item (model,data) values (?,?) returning id"
             ))?
-            .query_row(params![model, data], |r| Ok(r.get::<usize, i32>(0)?))
+            .query_row(params![model, data], |r| r.get::<usize, i32>(0))
             .wrap_err("retrieving item from db")?;
 
         // insert tags if any
@@ -49,7 +49,7 @@ pub mod item {
                     minify_sql!("select id from tag where name in ({})"),
                     template::vars(tags.len())
                 ))?
-                .query_map(params_from_iter(tags), |r| Ok(r.get::<usize, i32>(0)?))?
+                .query_map(params_from_iter(tags), |r| r.get::<usize, i32>(0))?
                 .map(|r| r.wrap_err("retrieve ids from tags"))
                 .collect::<Result<Vec<i32>>>()?;
             c.execute(
@@ -71,9 +71,9 @@ pub mod item {
         Ok(())
     }
     pub fn get_tags(c: &mut Connection, id: i32) -> Result<Vec<Tag>> {
-        let mut stmt = c.prepare(&format!(minify_sql!(
+        let mut stmt = c.prepare(minify_sql!(
             "select * from tag where id in (select tag_id from tag_item_map where item_id = ?1)"
-        )))?;
+        ))?;
         let tags: Vec<Tag> = stmt
             .query_map((id,), |r| {
                 Ok(Tag {
@@ -87,7 +87,8 @@ pub mod item {
             .collect::<Result<Vec<Tag>>>()?;
         Ok(tags)
     }
-    pub fn add_tags(c: &mut Connection, id: i32, tags: &[&str]) -> Result<()> {
+    /// give the item `tags`, returns the number of tags it did not have yet
+    pub fn add_tags(c: &Connection, id: i32, tags: &[&str]) -> Result<usize> {
         c.execute(
             &format!(
                 "insert or ignore into tag (name) values {}",
@@ -100,17 +101,17 @@ pub mod item {
                 "select id from tag where name in ({})",
                 template::vars(tags.len())
             ))?
-            .query_map(params_from_iter(tags), |r| Ok(r.get::<usize, i32>(0)?))?
+            .query_map(params_from_iter(tags), |r| r.get::<usize, i32>(0))?
             .map(|r| r.wrap_err("could not retrieve a id from the db"))
             .collect::<Result<Vec<i32>>>()?;
-        c.execute(
+        let n = c.execute(
             &format!(
                 "insert or ignore into tag_item_map (tag_id, item_id) values {}",
                 template::values(2, tags.len())
             ),
             params_from_iter(tag_ids.iter().flat_map(|tag_id| [*tag_id, id])),
         )?;
-        Ok(())
+        Ok(n)
     }
     pub fn remove_tags(c: &mut Connection, id: i32, tags: &[&str]) -> Result<()> {
         // first retrieve the tag ids
@@ -119,7 +120,7 @@ pub mod item {
                 "select id from tag where name in ({})",
                 template::vars(tags.len())
             ))?
-            .query_map(params_from_iter(tags), |r| Ok(r.get(0)?))?
+            .query_map(params_from_iter(tags), |r| r.get(0))?
             .map(|r| r.wrap_err("could not retrieve a tag from the db"))
             .collect::<Result<Vec<i32>>>()?;
         c.execute(
@@ -132,28 +133,80 @@ pub mod item {
         )?;
         Ok(())
     }
+    /// leave the item out of reviews, or include it again
+    pub fn set_suspended(c: &Connection, id: i32, suspended: bool) -> Result<()> {
+        c.execute(
+            "update item set suspended = ? where id == ?",
+            (suspended, id),
+        )?;
+        Ok(())
+    }
+    /// leave the item out of reviews until `until`, or include it again with `None`
+    pub fn set_buried_until(
+        c: &mut Connection,
+        id: i32,
+        until: Option<OffsetDateTime>,
+    ) -> Result<()> {
+        c.execute(
+            "update item set buried_until = ? where id == ?",
+            (until, id),
+        )?;
+        Ok(())
+    }
+    /// make the item a sibling of the items in `group`, or of none with `None`
+    pub fn set_sibling_group(c: &mut Connection, id: i32, group: Option<&str>) -> Result<()> {
+        c.execute(
+            "update item set sibling_group = ? where id == ?",
+            (group, id),
+        )?;
+        Ok(())
+    }
+    /// bury the siblings of item `id` until `until`, unless they are buried for longer
+    pub fn bury_siblings(
+        c: &Connection,
+        id: i32,
+        group: &str,
+        until: OffsetDateTime,
+    ) -> Result<usize> {
+        let n = c.execute(
+            minify_sql!(
+                "update item set buried_until = ?1
+                where sibling_group == ?2 and id != ?3 and
+                (buried_until is null or datetime(buried_until) < datetime(?1))"
+            ),
+            (until, group, id),
+        )?;
+        Ok(n)
+    }
     pub fn delete(c: &mut Connection, id: i32) -> Result<()> {
         c.execute("delete from item where id = ?1", (id,))?;
         Ok(())
     }
+    /// map a row of the item table (or one of its views) to an [`Item`]
+    pub fn from_row(r: &rusqlite::Row) -> rusqlite::Result<Item> {
+        Ok(Item {
+            id: r.get("id")?,
+            maturity: r.get("maturity")?,
+            stability: r.get("stability")?,
+            difficulty: r.get("difficulty")?,
+            last_review_date: r.get("last_review_date")?,
+            n_reviews: r.get("n_reviews")?,
+            n_lapses: r.get("n_lapses")?,
+            model: r.get("model")?,
+            data: r.get("data")?,
+            updated_at: r.get("updated_at")?,
+            created_at: r.get("created_at")?,
+            due_date: r.get("due_date")?,
+            learning_step: r.get("learning_step")?,
+            suspended: r.get("suspended")?,
+            buried_until: r.get("buried_until")?,
+            sibling_group: r.get("sibling_group")?,
+        })
+    }
     pub fn get(c: &mut Connection, id: i32) -> Result<Item> {
         let mut stmt = c.prepare("select * from item where id = ?1 limit 1")?;
         let item = stmt
-            .query_row((id,), |r| {
-                Ok(Item {
-                    id: r.get(0)?,
-                    maturity: r.get(1)?,
-                    stability: r.get(2)?,
-                    difficulty: r.get(3)?,
-                    last_review_date: r.get(4)?,
-                    n_reviews: r.get(5)?,
-                    n_lapses: r.get(6)?,
-                    model: r.get(7)?,
-                    data: r.get(8)?,
-                    updated_at: r.get(9)?,
-                    created_at: r.get(10)?,
-                })
-            })
+            .query_row((id,), from_row)
             .wrap_err("retriving item from db")?;
         Ok(item)
     }
@@ -162,6 +215,7 @@ pub mod item {
         filter_expr: Option<AstNode>,
         include_tags: &[&str],
         exclude_tags: &[&str],
+        order: Option<review::Order>,
     ) -> Result<Vec<Item>> {
         let filter_expr = filter_expr.map(|e| utils::filter_expr_to_sql(&e));
         let include_ids = if include_tags.is_empty() {
@@ -172,7 +226,7 @@ pub mod item {
                     "select item_id from tag_item_map where tag_id in (select id from tag where name in ({}))",
                     template::vars(include_tags.len())
                 ))?
-                .query_map(params_from_iter(include_tags), |r| Ok(r.get(0)?))?
+                .query_map(params_from_iter(include_tags), |r| r.get(0))?
                 .map(|r|r.wrap_err("could not retrieve i32 from db"))
                 .collect::<Result<Vec<i32>>>()?,
             )
@@ -185,45 +239,28 @@ pub mod item {
                     "select item_id from tag_item_map where tag_id in (select id from tag where name in ({}))",
                     template::vars(exclude_tags.len())
                 ))?
-                .query_map(params_from_iter(exclude_tags), |r| Ok(r.get(0)?))?
+                .query_map(params_from_iter(exclude_tags), |r| r.get(0))?
                 .map(|r|r.wrap_err("could not retrieve id from db"))
                 .collect::<Result<Vec<i32>>>()?,
             )
         };
-        let query = match filter_expr {
+        let mut query = match filter_expr {
             Some(expr) => format!("select * from item where {}", expr),
             None => "select * from item".into(),
         };
+        if let Some(order) = order {
+            query = format!("{query} order by {}", order.to_sql());
+        }
         let mut items: Vec<Item> = c
             .prepare(&query)?
-            .query_map([], |r| {
-                Ok(Item {
-                    id: r.get(0)?,
-                    maturity: r.get(1)?,
-                    stability: r.get(2)?,
-                    difficulty: r.get(3)?,
-                    last_review_date: r.get(4)?,
-                    n_reviews: r.get(5)?,
-                    n_lapses: r.get(6)?,
-                    model: r.get(7)?,
-                    data: r.get(8)?,
-                    updated_at: r.get(9)?,
-                    created_at: r.get(10)?,
-                })
-            })?
+            .query_map([], from_row)?
             .map(|r| r.wrap_err("could not retrieve item from db"))
             .collect::<Result<Vec<Item>>>()?;
         if let Some(include_ids) = include_ids {
-            items = items
-                .into_iter()
-                .filter(|item| include_ids.contains(&item.id))
-                .collect()
+            items.retain(|item| include_ids.contains(&item.id))
         }
         if let Some(exclude_ids) = exclude_ids {
-            items = items
-                .into_iter()
-                .filter(|item| !exclude_ids.contains(&item.id))
-                .collect()
+            items.retain(|item| !exclude_ids.contains(&item.id))
         }
         Ok(items)
     }
@@ -238,7 +275,7 @@ pub mod tag {
             .prepare("insert into tag (name) values (?1) returning id")
             .context("preparing sql statement")?;
         let mut id = stmt
-            .query_map((tag,), |r| Ok(r.get::<usize, i32>(0)?))
+            .query_map((tag,), |r| r.get::<usize, i32>(0))
             .context("retrieving tag from sql result")?;
 
         let id = match id.next() {
@@ -276,7 +313,7 @@ pub mod tag {
             ),
             None => "select * from tag".into(),
         };
-        Ok(c.prepare(&query)?
+        c.prepare(&query)?
             .query_map([], |r| {
                 Ok(Tag {
                     id: r.get(0)?,
@@ -286,7 +323,7 @@ pub mod tag {
                 })
             })?
             .map(|r| r.wrap_err("could not retrieve id from db"))
-            .collect::<Result<Vec<Tag>>>()?)
+            .collect::<Result<Vec<Tag>>>()
     }
 }
 
@@ -294,63 +331,165 @@ pub mod review {
     use filter_language::AstNode;
 
     use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use time::Date;
 
-    /// retrieve
-    pub fn study_new(c: &mut Connection, filter_expr: Option<AstNode>) -> Result<Option<Item>> {
-        let query = match filter_expr {
-            Some(expr) => format!(
-                "select * from new_item where {} limit 1",
-                utils::filter_expr_to_sql(&expr)
-            ),
-            None => "select * from new_item limit 1".into(),
-        };
-        let mut query = c.prepare(&query)?;
-        let mut item = query
-            .query_map([], |r| {
-                Ok(Item {
-                    id: r.get(0)?,
-                    maturity: r.get(1)?,
-                    stability: r.get(2)?,
-                    difficulty: r.get(3)?,
-                    last_review_date: r.get(4)?,
-                    n_reviews: r.get(5)?,
-                    n_lapses: r.get(6)?,
-                    model: r.get(7)?,
-                    data: r.get(8)?,
-                    updated_at: r.get(9)?,
-                    created_at: r.get(10)?,
-                })
-            })?
-            .filter_map(Result::ok);
-        Ok(item.next())
+    /// The order in which items are studied
+    #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize, clap::ValueEnum)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Order {
+        /// lowest probability of recall first
+        Retrievability,
+        /// most overdue relative to the interval first
+        Overdue,
+        /// highest difficulty first
+        Difficulty,
+        /// shuffled
+        Random,
+        /// oldest first
+        Created,
     }
-    pub fn study_due(c: &mut Connection, filter_expr: Option<AstNode>) -> Result<Option<Item>> {
-        let query = match filter_expr {
-            Some(expr) => format!(
-                "select * from due_item where {} limit 1",
-                utils::filter_expr_to_sql(&expr)
-            ),
-            None => "select * from due_item limit 1".into(),
-        };
-        let mut query = c.prepare(&query)?;
-        let mut item = query
-            .query_map([], |r| {
-                Ok(Item {
-                    id: r.get(0)?,
-                    maturity: r.get(1)?,
-                    stability: r.get(2)?,
-                    difficulty: r.get(3)?,
-                    last_review_date: r.get(4)?,
-                    n_reviews: r.get(5)?,
-                    n_lapses: r.get(6)?,
-                    model: r.get(7)?,
-                    data: r.get(8)?,
-                    updated_at: r.get(9)?,
-                    created_at: r.get(10)?,
-                })
-            })?
-            .filter_map(Result::ok);
-        Ok(item.next())
+
+    impl Order {
+        /// the `order by` clause of the order, with ties broken by id
+        pub fn to_sql(self) -> &'static str {
+            match self {
+                Order::Retrievability => "retrievability(stability, last_review_date) asc, id asc",
+                Order::Overdue => minify_sql!(
+                    "(julianday('now') - julianday(due_date)) /
+                    max(julianday(due_date) - julianday(last_review_date), 1.0 / 1440) desc,
+                    id asc"
+                ),
+                Order::Difficulty => "difficulty desc, id asc",
+                Order::Random => "random()",
+                Order::Created => "created_at asc, id asc",
+            }
+        }
+    }
+
+    /// The number of items studied since some point in time
+    #[derive(Default, Debug, PartialEq, Eq)]
+    pub struct Studied {
+        /// new items that were reviewed for the first time
+        pub new: u32,
+        /// reviews of learnt (young and tenured) items
+        pub reviews: u32,
+    }
+
+    /// Items that may not be studied as a daily limit has been reached
+    #[derive(Default, Debug)]
+    pub struct Capped {
+        /// the global limit
+        pub all: bool,
+        /// the limits of these tags
+        pub tags: Vec<TagName>,
+    }
+
+    impl Capped {
+        /// the condition on items that are not capped
+        fn to_sql(&self) -> String {
+            match (self.all, self.tags.len()) {
+                (true, _) => "false".into(),
+                (false, 0) => "true".into(),
+                (false, n) => format!(
+                    "id not in (select item_id from tag_item_map
+                    where tag_id in (select id from tag where name in ({})))",
+                    template::vars(n)
+                ),
+            }
+        }
+    }
+
+    /// the number of items studied since `since`, of the items with `tag` if given
+    pub fn n_studied(
+        c: &mut Connection,
+        since: OffsetDateTime,
+        tag: Option<&str>,
+    ) -> Result<Studied> {
+        // reviews logged before the maturity was kept are told apart by their stability
+        let query = minify_sql!(
+            "select
+                count(*) filter (where coalesce(maturity_before == 'new', stability_before == 0.0)),
+                count(*) filter (where coalesce(
+                    maturity_before in ('young', 'tenured'),
+                    stability_before > 0.0
+                ))
+            from review_log
+            where julianday(review_date) >= julianday(?1)
+            and (?2 is null or item_id in (
+                select item_id from tag_item_map
+                where tag_id in (select id from tag where name == ?2)
+            ))"
+        );
+        let studied = c.query_row(query, params![since, tag], |r| {
+            Ok(Studied {
+                new: r.get(0)?,
+                reviews: r.get(1)?,
+            })
+        })?;
+        Ok(studied)
+    }
+
+    /// retrieve the next new item that is not capped
+    pub fn study_new(
+        c: &mut Connection,
+        filter_expr: Option<AstNode>,
+        order: Order,
+        capped: &Capped,
+    ) -> Result<Option<Item>> {
+        Ok(new_items(c, filter_expr, order, capped, 1)?.pop())
+    }
+    /// retrieve the next due item. (re)learning items always come first, as their steps
+    /// are only minutes apart, and are never capped.
+    pub fn study_due(
+        c: &mut Connection,
+        filter_expr: Option<AstNode>,
+        order: Order,
+        capped: &Capped,
+    ) -> Result<Option<Item>> {
+        Ok(due_items(c, filter_expr, order, capped, 1)?.pop())
+    }
+    /// retrieve the next `limit` new items that are not capped, see [`study_new`]
+    pub fn new_items(
+        c: &mut Connection,
+        filter_expr: Option<AstNode>,
+        order: Order,
+        capped: &Capped,
+        limit: usize,
+    ) -> Result<Vec<Item>> {
+        let filter = filter_expr.map_or("true".into(), |e| utils::filter_expr_to_sql(&e));
+        let query = format!(
+            "select * from new_item where {filter} and {} order by {} limit {limit}",
+            capped.to_sql(),
+            order.to_sql()
+        );
+        c.prepare(&query)?
+            .query_map(params_from_iter(&capped.tags), item::from_row)?
+            .map(|r| r.wrap_err("could not retrieve item from db"))
+            .collect()
+    }
+    /// retrieve the next `limit` due items, see [`study_due`]
+    pub fn due_items(
+        c: &mut Connection,
+        filter_expr: Option<AstNode>,
+        order: Order,
+        capped: &Capped,
+        limit: usize,
+    ) -> Result<Vec<Item>> {
+        let filter = filter_expr.map_or("true".into(), |e| utils::filter_expr_to_sql(&e));
+        let query = format!(
+            "select * from due_item
+            where {filter} and (maturity in ('learning', 'relearning') or {})
+            order by maturity in ('learning', 'relearning') desc, {}
+            limit {limit}",
+            capped.to_sql(),
+            order.to_sql()
+        );
+        c.prepare(&query)?
+            .query_map(params_from_iter(&capped.tags), item::from_row)?
+            .map(|r| r.wrap_err("could not retrieve item from db"))
+            .collect()
     }
     pub fn query_n_due(c: &mut Connection, filter_expr: Option<AstNode>) -> Result<i32> {
         let query = match filter_expr {
@@ -362,7 +501,7 @@ pub mod review {
         };
         let mut query = c.prepare(&query)?;
         let item = query
-            .query_map([], |r| Ok(r.get(0)?))?
+            .query_map([], |r| r.get(0))?
             .filter_map(Result::ok)
             .next()
             .map(|x: Option<i32>| x.unwrap_or(0))
@@ -379,7 +518,7 @@ pub mod review {
         };
         let mut query = c.prepare(&query)?;
         let item = query
-            .query_map([], |r| Ok(r.get(0)?))?
+            .query_map([], |r| r.get(0))?
             .filter_map(Result::ok)
             .next()
             .map(|x: Option<i32>| x.unwrap_or(0))
@@ -387,41 +526,433 @@ pub mod review {
         item
     }
 
-    /// used when the item is new and we failed a review (or just want to see it again)
-    pub fn increment_n_reviews(c: &mut Connection, id: i32) -> Result<()> {
+    /// persist the outcome of a review, see [`sra::schedule`]
+    pub fn set_card_state(
+        c: &Connection,
+        id: i32,
+        state: &sra::CardState,
+        due_date: OffsetDateTime,
+    ) -> Result<()> {
         c.execute(
-            "update item set n_reviews = n_reviews + 1 where id == ?",
-            [id],
+            minify_sql!(
+                "update item set
+                    maturity = ?,
+                    stability = ?,
+                    difficulty = ?,
+                    last_review_date = ?,
+                    n_reviews = ?,
+                    n_lapses = ?,
+                    learning_step = ?,
+                    due_date = ?
+                where id == ?"
+            ),
+            params![
+                Maturity::from(state.maturity),
+                state.s,
+                state.d,
+                state.last_review,
+                state.reps,
+                state.lapses,
+                state.step,
+                due_date,
+                id
+            ],
         )?;
         Ok(())
     }
 
-    pub fn increment_n_lapses(c: &mut Connection, id: i32) -> Result<()> {
+    /// the number of scheduled items (not new, (re)learning or suspended) due on each study
+    /// day from `from` on
+    pub fn n_due_per_day(c: &mut Connection, from: Date) -> Result<BTreeMap<Date, u32>> {
+        c.prepare(minify_sql!(
+            "select study_date(due_date), count(*) from item
+            where maturity in ('young', 'tenured') and not suspended and study_date(due_date) >= ?
+            group by study_date(due_date)"
+        ))?
+        .query_map([from], |r| Ok((r.get(0)?, r.get(1)?)))?
+        .map(|r| r.wrap_err("could not retrieve due count from db"))
+        .collect()
+    }
+}
+
+pub mod review_log {
+    use filter_language::AstNode;
+    use rusqlite::types::Type;
+    use rusqlite::OptionalExtension;
+    use sra::model::{Grade, ModelData, Time};
+
+    use super::*;
+
+    /// record a scored review of `before`, the item as it was before the review, together
+    /// with the sra parameters after the review was applied and how long it took
+    pub fn add(
+        c: &Connection,
+        grade: Grade,
+        review_date: OffsetDateTime,
+        elapsed_days: Time,
+        before: &Item,
+        after: ModelData,
+        duration_ms: Option<u32>,
+    ) -> Result<i32> {
+        let id = c
+            .prepare(minify_sql!(
+                "insert into review_log (
+                    item_id,
+                    grade,
+                    review_date,
+                    elapsed_days,
+                    stability_before,
+                    difficulty_before,
+                    stability_after,
+                    difficulty_after,
+                    maturity_before,
+                    last_review_date_before,
+                    n_reviews_before,
+                    n_lapses_before,
+                    learning_step_before,
+                    due_date_before,
+                    suspended_before,
+                    duration_ms
+                ) values (?,?,?,?,?,?,?,?,?,?,?,?,?,?,?,?) returning id"
+            ))?
+            .query_row(
+                params![
+                    before.id,
+                    grade as u8,
+                    review_date,
+                    elapsed_days,
+                    before.stability,
+                    before.difficulty,
+                    after.s,
+                    after.d,
+                    before.maturity,
+                    before.last_review_date,
+                    before.n_reviews,
+                    before.n_lapses,
+                    before.learning_step,
+                    before.due_date,
+                    before.suspended,
+                    duration_ms
+                ],
+                |r| r.get::<usize, i32>(0),
+            )
+            .wrap_err("inserting review log entry")?;
+        Ok(id)
+    }
+
+    /// remember that review `id` gave the item the leech tag `tag`
+    pub fn set_leech_tag(c: &Connection, id: i32, tag: &str) -> Result<()> {
         c.execute(
-            "update item set n_lapses = n_lapses + 1 where id == ?",
-            [id],
+            "update review_log set leech_tag = ? where id == ?",
+            params![tag, id],
         )?;
         Ok(())
     }
-    /// update sra parameters
-    pub fn set_maturity(c: &mut Connection, id: i32, maturity: Maturity) -> Result<()> {
-        c.execute("update item set maturity = ? where id == ?", (maturity, id))?;
-        Ok(())
+
+    /// undo the most recently scored review, of item `item_id` if given. The item is
+    /// restored to its state from before the review, including the leech tag it was given,
+    /// and the review is removed from the log. Returns the id of the item, `None` if there
+    /// is no review to undo.
+    pub fn undo(c: &mut Connection, item_id: Option<i32>) -> Result<Option<i32>> {
+        let tx = c.transaction()?;
+        let last = tx
+            .query_row(
+                minify_sql!(
+                    "select id, item_id, maturity_before is not null from review_log
+                    where ?1 is null or item_id == ?1
+                    order by id desc limit 1"
+                ),
+                [item_id],
+                |r| {
+                    Ok((
+                        r.get::<usize, i32>(0)?,
+                        r.get::<usize, i32>(1)?,
+                        r.get::<usize, bool>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((log_id, item_id, undoable)) = last else {
+            return Ok(None);
+        };
+        if !undoable {
+            return Err(eyre!(
+                "the last review of item {item_id} was scored before reviews could be undone"
+            ));
+        }
+        tx.execute(
+            minify_sql!(
+                "update item set
+                    maturity = l.maturity_before,
+                    stability = l.stability_before,
+                    difficulty = l.difficulty_before,
+                    last_review_date = l.last_review_date_before,
+                    n_reviews = l.n_reviews_before,
+                    n_lapses = l.n_lapses_before,
+                    learning_step = l.learning_step_before,
+                    due_date = l.due_date_before,
+                    suspended = l.suspended_before
+                from (select * from review_log where id == ?1) as l
+                where item.id == l.item_id"
+            ),
+            [log_id],
+        )?;
+        tx.execute(
+            minify_sql!(
+                "delete from tag_item_map where (item_id, tag_id) in (
+                    select l.item_id, tag.id from review_log as l join tag on tag.name == l.leech_tag
+                    where l.id == ?
+                )"
+            ),
+            [log_id],
+        )?;
+        tx.execute("delete from review_log where id == ?", [log_id])?;
+        tx.commit()?;
+        Ok(Some(item_id))
     }
-    /// update sra parameters
-    pub fn set_sra_params(
-        c: &mut Connection,
-        id: i32,
-        stability: f32,
-        difficulty: f32,
-        review_date: OffsetDateTime,
-    ) -> Result<()> {
+
+    /// retrieve the review history, oldest review first
+    pub fn query(c: &mut Connection, filter_expr: Option<AstNode>) -> Result<Vec<ReviewLog>> {
+        let query = match filter_expr {
+            Some(expr) => format!(
+                "select * from review_log where {} order by review_date, id",
+                utils::filter_expr_to_sql(&expr)
+            ),
+            None => "select * from review_log order by review_date, id".into(),
+        };
+        c.prepare(&query)?
+            .query_map([], |r| {
+                Ok(ReviewLog {
+                    id: r.get(0)?,
+                    item_id: r.get(1)?,
+                    grade: Grade::try_from(r.get::<usize, u8>(2)?).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(2, Type::Integer, e.into())
+                    })?,
+                    review_date: r.get(3)?,
+                    elapsed_days: r.get(4)?,
+                    stability_before: r.get(5)?,
+                    difficulty_before: r.get(6)?,
+                    stability_after: r.get(7)?,
+                    difficulty_after: r.get(8)?,
+                    created_at: r.get(9)?,
+                    duration_ms: r.get("duration_ms")?,
+                })
+            })?
+            .map(|r| r.wrap_err("could not retrieve review log entry from db"))
+            .collect::<Result<Vec<ReviewLog>>>()
+    }
+}
+
+pub mod session {
+    use rusqlite::OptionalExtension;
+    use serde::Serialize;
+
+    use super::*;
+
+    /// The number of reviews with each grade
+    #[derive(Serialize, Default, Debug, PartialEq, Eq)]
+    pub struct Grades {
+        pub again: u32,
+        pub hard: u32,
+        pub good: u32,
+        pub easy: u32,
+    }
+
+    /// What a study session accomplished
+    #[derive(Serialize, Debug)]
+    pub struct Summary {
+        pub id: i32,
+        #[serde(with = "time::serde::rfc3339")]
+        pub started_at: OffsetDateTime,
+        #[serde(with = "time::serde::rfc3339::option")]
+        pub ended_at: Option<OffsetDateTime>,
+        /// the number of reviews scored in the session
+        pub n_reviews: u32,
+        /// the number of items left in the queue
+        pub n_remaining: u32,
+        pub grades: Grades,
+        /// seconds from the start until the end, or until now while the session is open
+        pub time_spent_s: f32,
+        /// the summed response time of the reviews with a recorded duration
+        pub response_time_ms: Option<u64>,
+        /// the share of the reviews of learnt (young and tenured) items that were
+        /// recalled, null without such reviews
+        pub retention: Option<f32>,
+    }
+
+    /// the id of the open session, if any
+    pub fn open(c: &Connection) -> Result<Option<i32>> {
+        let id = c
+            .query_row(
+                "select id from session where ended_at is null order by id desc limit 1",
+                [],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    /// start a session serving `items` in order
+    pub fn start(c: &mut Connection, items: &[i32]) -> Result<i32> {
+        let tx = c.transaction()?;
+        let id: i32 = tx.query_row("insert into session default values returning id", [], |r| {
+            r.get(0)
+        })?;
+        if !items.is_empty() {
+            tx.execute(
+                &format!(
+                    "insert into session_item (session_id, item_id, position) values {}",
+                    template::values(3, items.len())
+                ),
+                params_from_iter(
+                    items
+                        .iter()
+                        .enumerate()
+                        .flat_map(|(position, item)| [id, *item, position as i32]),
+                ),
+            )?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// the id of the next item of `session` that has not been scored
+    pub fn next(c: &mut Connection, session: i32) -> Result<Option<i32>> {
+        let id = c
+            .query_row(
+                minify_sql!(
+                    "select item_id from session_item
+                    where session_id == ? and review_id is null
+                    order by position, id limit 1"
+                ),
+                [session],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    /// record `review`, a review of `item_id`, in `session`. Given `again_offset` the item is
+    /// put back in the queue, after that many of the items that follow. Whether the item
+    /// was waiting in the session. Meant to run in the transaction that scores the review.
+    pub fn record(
+        c: &Connection,
+        session: i32,
+        item_id: i32,
+        review: i32,
+        again_offset: Option<u32>,
+    ) -> Result<bool> {
+        let entry: Option<(i32, i32)> = c
+            .query_row(
+                minify_sql!(
+                    "select id, position from session_item
+                    where session_id == ? and item_id == ? and review_id is null
+                    order by position, id limit 1"
+                ),
+                [session, item_id],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .optional()?;
+        let Some((entry, position)) = entry else {
+            return Ok(false);
+        };
+        c.execute(
+            "update session_item set review_id = ? where id == ?",
+            [review, entry],
+        )?;
+        if let Some(offset) = again_offset {
+            // the position of the item that follows `offset` items, or the end of the queue
+            let position: i32 = c.query_row(
+                minify_sql!(
+                    "select coalesce(
+                        (select position from session_item
+                        where session_id == ?1 and review_id is null and position > ?2
+                        order by position limit 1 offset ?3),
+                        (select max(position) + 1 from session_item where session_id == ?1)
+                    )"
+                ),
+                [session, position, offset as i32],
+                |r| r.get(0),
+            )?;
+            c.execute(
+                minify_sql!(
+                    "update session_item set position = position + 1
+                    where session_id == ? and position >= ?"
+                ),
+                [session, position],
+            )?;
+            c.execute(
+                minify_sql!(
+                    "insert into session_item (session_id, item_id, position, again_review_id)
+                    values (?, ?, ?, ?)"
+                ),
+                [session, item_id, position, review],
+            )?;
+        }
+        Ok(true)
+    }
+
+    /// end `session` at `now`
+    pub fn end(c: &mut Connection, session: i32, now: OffsetDateTime) -> Result<()> {
         c.execute(
-            "update item set stability = ?, difficulty = ?, last_review_date = ?  where id == ?",
-            (stability, difficulty, review_date, id),
+            "update session set ended_at = ? where id == ?",
+            params![now, session],
         )?;
         Ok(())
     }
+
+    /// summarize `session`
+    pub fn summary(c: &mut Connection, session: i32) -> Result<Summary> {
+        let mut summary = c
+            .query_row(
+                minify_sql!(
+                    "select
+                        s.started_at,
+                        s.ended_at,
+                        count(l.id),
+                        count(si.id) filter (where si.review_id is null),
+                        count(*) filter (where l.grade == 1),
+                        count(*) filter (where l.grade == 2),
+                        count(*) filter (where l.grade == 3),
+                        count(*) filter (where l.grade == 4),
+                        sum(l.duration_ms),
+                        count(*) filter (where l.maturity_before in ('young', 'tenured')),
+                        count(*) filter (where
+                            l.maturity_before in ('young', 'tenured') and l.grade > 1
+                        )
+                    from session s
+                    left join session_item si on si.session_id == s.id
+                    left join review_log l on l.id == si.review_id
+                    where s.id == ?
+                    group by s.id"
+                ),
+                [session],
+                |r| {
+                    let learnt: u32 = r.get(9)?;
+                    let recalled: u32 = r.get(10)?;
+                    Ok(Summary {
+                        id: session,
+                        started_at: r.get(0)?,
+                        ended_at: r.get(1)?,
+                        n_reviews: r.get(2)?,
+                        n_remaining: r.get(3)?,
+                        grades: Grades {
+                            again: r.get(4)?,
+                            hard: r.get(5)?,
+                            good: r.get(6)?,
+                            easy: r.get(7)?,
+                        },
+                        time_spent_s: 0.0,
+                        response_time_ms: r.get(8)?,
+                        retention: (learnt > 0).then(|| recalled as f32 / learnt as f32),
+                    })
+                },
+            )
+            .wrap_err(format!("there is no session {session}"))?;
+        let end = summary.ended_at.unwrap_or_else(OffsetDateTime::now_utc);
+        summary.time_spent_s = (end - summary.started_at).as_seconds_f32();
+        Ok(summary)
+    }
 }
 
 #[cfg(test)]
@@ -429,7 +960,7 @@ mod tests {
     use super::*;
 
     fn init() -> Result<DB> {
-        DB::open(":memory:")
+        DB::open(":memory:", &sra::Parameters::default(), Default::default())
     }
 
     #[test]
@@ -474,8 +1005,8 @@ mod tests {
         let id = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]).unwrap();
 
         let tags_to_add = vec!["test", "test2"];
-        item::add_tags(&mut c, id, &tags_to_add).unwrap();
-        item::add_tags(&mut c, id, &tags_to_add).unwrap();
+        item::add_tags(&c, id, &tags_to_add).unwrap();
+        item::add_tags(&c, id, &tags_to_add).unwrap();
         let tags = item::get_tags(&mut c, id).unwrap();
         let tags: Vec<String> = tags.into_iter().map(|t| t.name).collect();
         assert_eq!(tags_to_add, tags);
@@ -525,13 +1056,13 @@ mod tests {
             .collect();
         assert_eq!(item_2_tags, item_tags);
 
-        let items = item::query(&mut c, None, &["test1"], &[]).unwrap();
+        let items = item::query(&mut c, None, &["test1"], &[], None).unwrap();
         assert_eq!(items[0].id, id1);
 
-        let items = item::query(&mut c, None, &["test3"], &[]).unwrap();
+        let items = item::query(&mut c, None, &["test3"], &[], None).unwrap();
         assert_eq!(items[0].id, id2);
 
-        let items = item::query(&mut c, None, &[], &["test2"]).unwrap();
+        let items = item::query(&mut c, None, &[], &["test2"], None).unwrap();
         assert!(items.is_empty());
         Ok(())
     }
@@ -555,6 +1086,550 @@ mod tests {
         Ok(())
     }
     // -------------
+    // ==== review ====
+    #[test]
+    fn test_due_items_follow_due_date() -> Result<()> {
+        use time::Duration;
+        let mut c = init()?;
+        let id1 = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]).unwrap();
+        let id2 = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]).unwrap();
+        let now = OffsetDateTime::now_utc();
+        let young = sra::CardState {
+            maturity: sra::model::Maturity::Young,
+            s: 10.0,
+            d: 5.0,
+            last_review: now,
+            reps: 1,
+            lapses: 0,
+            step: 0,
+        };
+        // same stability, but only the first item has reached its due date
+        review::set_card_state(&c, id1, &young, now - Duration::DAY)?;
+        review::set_card_state(&c, id2, &young, now + Duration::DAY)?;
+
+        assert_eq!(review::query_n_due(&mut c, None)?, 1);
+        assert_eq!(
+            review::study_due(
+                &mut c,
+                None,
+                review::Order::Retrievability,
+                &Default::default()
+            )?
+            .unwrap()
+            .id,
+            id1
+        );
+        assert_eq!(review::query_n_new(&mut c, None)?, 0);
+        Ok(())
+    }
+    #[test]
+    fn test_learning_items_are_due_at_their_due_time() -> Result<()> {
+        use time::Duration;
+        let mut c = init()?;
+        let now = OffsetDateTime::now_utc();
+        let state = |maturity| sra::CardState {
+            maturity,
+            s: 1.0,
+            d: 5.0,
+            last_review: now,
+            reps: 1,
+            lapses: 0,
+            step: 0,
+        };
+        let add =
+            |c: &mut Connection| item::add(c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]);
+        let learning = add(&mut c)?;
+        let relearning = add(&mut c)?;
+        let later = add(&mut c)?;
+        use sra::model::Maturity::*;
+        review::set_card_state(&c, learning, &state(Learning), now - Duration::MINUTE)?;
+        review::set_card_state(&c, relearning, &state(Relearning), now - Duration::MINUTE)?;
+        review::set_card_state(&c, later, &state(Learning), now + Duration::MINUTE)?;
+
+        // learning items come first, and only once their due time has passed
+        assert_eq!(review::query_n_due(&mut c, None)?, 2);
+        assert!([learning, relearning].contains(
+            &review::study_due(
+                &mut c,
+                None,
+                review::Order::Retrievability,
+                &Default::default()
+            )?
+            .unwrap()
+            .id
+        ));
+        Ok(())
+    }
+    #[test]
+    fn test_sra_functions() -> Result<()> {
+        use time::Duration;
+        let mut c = init()?;
+        let now = OffsetDateTime::now_utc();
+        let state = |s, last_review| sra::CardState {
+            maturity: sra::model::Maturity::Young,
+            s,
+            d: 5.0,
+            last_review,
+            reps: 1,
+            lapses: 0,
+            step: 0,
+        };
+        let add =
+            |c: &mut Connection| item::add(c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]);
+        let new = add(&mut c)?;
+        let forgotten = add(&mut c)?;
+        let remembered = add(&mut c)?;
+        let ten_days_ago = now - Duration::days(10);
+        review::set_card_state(&c, forgotten, &state(10.0, ten_days_ago), now)?;
+        review::set_card_state(&c, remembered, &state(100.0, ten_days_ago), now)?;
+
+        let r = |c: &mut Connection, id: i32| -> Result<Option<f64>> {
+            Ok(c.query_row(
+                "select retrievability(stability, last_review_date) from item where id = ?",
+                [id],
+                |r| r.get(0),
+            )?)
+        };
+        assert_eq!(r(&mut c, new)?, None);
+        // at 10 days a stability of 10 days gives 90%
+        assert!((r(&mut c, forgotten)?.unwrap() - 0.9).abs() < 1e-3);
+        assert!(r(&mut c, remembered)?.unwrap() > 0.9);
+        let elapsed: f64 = c.query_row(
+            "select elapsed_days(last_review_date) from item where id = ?",
+            [forgotten],
+            |r| r.get(0),
+        )?;
+        assert!((elapsed - 10.0).abs() < 1e-3);
+        let due: OffsetDateTime = c.query_row(
+            "select due_date(stability, last_review_date) from item where id = ?",
+            [forgotten],
+            |r| r.get(0),
+        )?;
+        assert!((due - now).abs() < Duration::MINUTE);
+        let due_at = |c: &mut Connection, r: f64| -> rusqlite::Result<Option<OffsetDateTime>> {
+            c.query_row(
+                "select due_date(stability, last_review_date, ?) from item where id = ?",
+                params![r, forgotten],
+                |r| r.get(0),
+            )
+        };
+        assert!(due_at(&mut c, 0.0).is_err());
+        assert!(due_at(&mut c, 1.0).is_err());
+        // further away than a date can hold
+        assert_eq!(due_at(&mut c, 1e-5)?, None);
+        assert_eq!(due_at(&mut c, 1e-30)?, None);
+
+        // usable in filters, and the least likely to be recalled is studied first
+        let filter = filter_language::FilterLangParser::parse("retrievability < 0.95")?;
+        let items = item::query(&mut c, Some(filter), &[], &[], None)?;
+        assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), [forgotten]);
+        assert_eq!(
+            review::study_due(
+                &mut c,
+                None,
+                review::Order::Retrievability,
+                &Default::default()
+            )?
+            .unwrap()
+            .id,
+            forgotten
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_due_orders() -> Result<()> {
+        use review::Order::*;
+        use time::Duration;
+        let mut c = init()?;
+        let now = OffsetDateTime::now_utc();
+        // reviewed `elapsed` days ago, due `overdue` days ago
+        let mut add = |s, d, elapsed, overdue| -> Result<i32> {
+            let id = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[])?;
+            let state = sra::CardState {
+                maturity: sra::model::Maturity::Young,
+                s,
+                d,
+                last_review: now - Duration::days(elapsed),
+                reps: 1,
+                lapses: 0,
+                step: 0,
+            };
+            review::set_card_state(&c, id, &state, now - Duration::days(overdue))?;
+            Ok(id)
+        };
+        let a = add(20.0, 3.0, 30, 10)?;
+        let b = add(5.0, 8.0, 10, 5)?;
+        let d = add(50.0, 5.0, 60, 10)?;
+        let next = |c: &mut Connection, order| -> Result<i32> {
+            Ok(review::study_due(c, None, order, &Default::default())?
+                .unwrap()
+                .id)
+        };
+        assert_eq!(next(&mut c, Retrievability)?, b);
+        assert_eq!(next(&mut c, Overdue)?, b);
+        assert_eq!(next(&mut c, Difficulty)?, b);
+        assert_eq!(next(&mut c, Created)?, a);
+        assert!([a, b, d].contains(&next(&mut c, Random)?));
+
+        let ids = |items: Vec<Item>| items.iter().map(|i| i.id).collect::<Vec<_>>();
+        let items = item::query(&mut c, None, &[], &[], Some(Difficulty))?;
+        assert_eq!(ids(items), [b, d, a]);
+        let items = item::query(&mut c, None, &[], &[], Some(Retrievability))?;
+        assert_eq!(ids(items), [b, a, d]);
+        let items = review::due_items(&mut c, None, Difficulty, &Default::default(), 2)?;
+        assert_eq!(ids(items), [b, d]);
+        Ok(())
+    }
+    #[test]
+    fn test_n_due_per_day() -> Result<()> {
+        use time::Duration;
+        let mut c = init()?;
+        let now = OffsetDateTime::now_utc();
+        let state = |maturity| sra::CardState {
+            maturity,
+            s: 10.0,
+            d: 5.0,
+            last_review: now,
+            reps: 1,
+            lapses: 0,
+            step: 0,
+        };
+        use sra::model::Maturity::*;
+        for (maturity, days) in [
+            (Young, 3),
+            (Tenured, 3),
+            (Young, 5),
+            (Learning, 5),
+            (Young, -1),
+        ] {
+            let id = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[])?;
+            review::set_card_state(&c, id, &state(maturity), now + Duration::days(days))?;
+        }
+        let day = |d| (now + Duration::days(d)).date();
+        let counts = review::n_due_per_day(&mut c, now.date())?;
+        assert_eq!(counts, [(day(3), 2), (day(5), 1)].into());
+        Ok(())
+    }
+    #[test]
+    fn test_due_on_study_days() -> Result<()> {
+        use sra::day::StudyDay;
+        use time::Duration;
+        let day = StudyDay::new("Asia/Tokyo", 4).unwrap();
+        let mut c = DB::open(":memory:", &sra::Parameters::default(), day)?;
+        let now = OffsetDateTime::now_utc();
+        let young = sra::CardState {
+            maturity: sra::model::Maturity::Young,
+            s: 10.0,
+            d: 5.0,
+            last_review: now - Duration::days(10),
+            reps: 3,
+            lapses: 0,
+            step: 0,
+        };
+        // due at the end of today and at the start of tomorrow
+        let tomorrow = day.next(now);
+        let data = r#"{"front":"foo","back":"bar"}"#;
+        let today_id = item::add(&mut c, "flashcard", data, &[])?;
+        review::set_card_state(&c, today_id, &young, tomorrow - Duration::MINUTE)?;
+        let tomorrow_id = item::add(&mut c, "flashcard", data, &[])?;
+        review::set_card_state(&c, tomorrow_id, &young, tomorrow)?;
+
+        assert_eq!(review::query_n_due(&mut c, None)?, 1);
+        let order = review::Order::Created;
+        let due = review::study_due(&mut c, None, order, &Default::default())?;
+        assert_eq!(due.unwrap().id, today_id);
+        let today = day.date(now);
+        let counts = review::n_due_per_day(&mut c, today)?;
+        assert_eq!(counts, [(today, 1), (today + Duration::DAY, 1)].into());
+        Ok(())
+    }
+    #[test]
+    fn test_suspended_and_buried_items_are_not_studied() -> Result<()> {
+        let mut c = init()?;
+        let id = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[])?;
+        let order = review::Order::Created;
+        item::set_suspended(&c, id, true)?;
+        assert!(item::get(&mut c, id)?.suspended);
+        assert_eq!(review::query_n_new(&mut c, None)?, 0);
+        assert!(review::study_new(&mut c, None, order, &Default::default())?.is_none());
+        item::set_suspended(&c, id, false)?;
+        assert_eq!(
+            review::study_new(&mut c, None, order, &Default::default())?
+                .unwrap()
+                .id,
+            id
+        );
+
+        let now = OffsetDateTime::now_utc();
+        item::set_buried_until(&mut c, id, Some(now + time::Duration::HOUR))?;
+        assert_eq!(review::query_n_new(&mut c, None)?, 0);
+        item::set_buried_until(&mut c, id, Some(now - time::Duration::HOUR))?;
+        assert_eq!(review::query_n_new(&mut c, None)?, 1);
+        Ok(())
+    }
+    #[test]
+    fn test_bury_siblings() -> Result<()> {
+        use time::Duration;
+        let mut c = init()?;
+        let add =
+            |c: &mut Connection| item::add(c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]);
+        let (front, back, other) = (add(&mut c)?, add(&mut c)?, add(&mut c)?);
+        item::set_sibling_group(&mut c, front, Some("foo"))?;
+        item::set_sibling_group(&mut c, back, Some("foo"))?;
+        let filter = filter_language::FilterLangParser::parse("sibling_group == 'foo'")?;
+        assert_eq!(item::query(&mut c, Some(filter), &[], &[], None)?.len(), 2);
+
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(
+            item::bury_siblings(&c, front, "foo", now + Duration::DAY)?,
+            1
+        );
+        assert!(item::get(&mut c, back)?.buried_until.is_some());
+        assert!(item::get(&mut c, front)?.buried_until.is_none());
+        assert!(item::get(&mut c, other)?.buried_until.is_none());
+        // items buried for longer stay buried
+        item::set_buried_until(&mut c, back, Some(now + Duration::WEEK))?;
+        assert_eq!(
+            item::bury_siblings(&c, front, "foo", now + Duration::DAY)?,
+            0
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_daily_limits() -> Result<()> {
+        use review::{Capped, Studied};
+        use sra::model::{Grade, Maturity::*, ModelData};
+        use time::Duration;
+        let mut c = init()?;
+        let now = OffsetDateTime::now_utc();
+        let data = r#"{"front":"foo","back":"bar"}"#;
+        let exam = item::add(&mut c, "flashcard", data, &["exam"])?;
+        let other = item::add(&mut c, "flashcard", data, &[])?;
+        let after = || ModelData { s: 1.0, d: 5.0 };
+        let before = item::get(&mut c, exam)?;
+        review_log::add(&c, Grade::Good, now, 0.0, &before, after(), None)?;
+
+        let hour_ago = now - Duration::HOUR;
+        let new = Studied { new: 1, reviews: 0 };
+        assert_eq!(review::n_studied(&mut c, hour_ago, None)?, new);
+        assert_eq!(review::n_studied(&mut c, hour_ago, Some("exam"))?, new);
+        assert_eq!(
+            review::n_studied(&mut c, hour_ago, Some("vocab"))?,
+            Studied::default()
+        );
+        assert_eq!(
+            review::n_studied(&mut c, now + Duration::HOUR, None)?,
+            Studied::default()
+        );
+
+        // the items of a capped tag are left out, and nothing is left when all are capped
+        let order = review::Order::Created;
+        let tags = Capped {
+            all: false,
+            tags: vec!["exam".into()],
+        };
+        let all = Capped {
+            all: true,
+            tags: vec![],
+        };
+        assert_eq!(
+            review::study_new(&mut c, None, order, &Default::default())?
+                .unwrap()
+                .id,
+            exam
+        );
+        assert_eq!(
+            review::study_new(&mut c, None, order, &tags)?.unwrap().id,
+            other
+        );
+        assert!(review::study_new(&mut c, None, order, &all)?.is_none());
+
+        // reviews of learnt items are capped, (re)learning items are not
+        let state = |maturity| sra::CardState {
+            maturity,
+            s: 10.0,
+            d: 5.0,
+            last_review: now - Duration::days(10),
+            reps: 3,
+            lapses: 0,
+            step: 0,
+        };
+        review::set_card_state(&c, exam, &state(Young), hour_ago)?;
+        let before = item::get(&mut c, exam)?;
+        review_log::add(&c, Grade::Good, now, 10.0, &before, after(), None)?;
+        let studied = Studied { new: 1, reviews: 1 };
+        assert_eq!(review::n_studied(&mut c, hour_ago, None)?, studied);
+        assert!(review::study_due(&mut c, None, order, &tags)?.is_none());
+        review::set_card_state(&c, other, &state(Relearning), hour_ago)?;
+        assert_eq!(
+            review::study_due(&mut c, None, order, &all)?.unwrap().id,
+            other
+        );
+        Ok(())
+    }
+    #[test]
+    fn test_session() -> Result<()> {
+        use sra::model::{Grade, ModelData};
+        let mut c = init()?;
+        let now = OffsetDateTime::now_utc();
+        let data = r#"{"front":"foo","back":"bar"}"#;
+        let mut ids = vec![];
+        for _ in 0..3 {
+            ids.push(item::add(&mut c, "flashcard", data, &[])?);
+        }
+        let [a, b, d] = ids[..] else { unreachable!() };
+        assert_eq!(session::open(&c)?, None);
+        let id = session::start(&mut c, &ids)?;
+        assert_eq!(session::open(&c)?, Some(id));
+
+        let score = |c: &mut Connection, item: i32, grade: Grade| -> Result<()> {
+            let before = item::get(c, item)?;
+            let after = ModelData { s: 1.0, d: 5.0 };
+            let review = review_log::add(c, grade, now, 0.0, &before, after, Some(1000))?;
+            let again = (grade == Grade::Again).then_some(1);
+            assert!(session::record(c, id, item, review, again)?);
+            Ok(())
+        };
+        // a failed item comes back after one more item
+        assert_eq!(session::next(&mut c, id)?, Some(a));
+        score(&mut c, a, Grade::Again)?;
+        assert_eq!(session::next(&mut c, id)?, Some(b));
+        score(&mut c, b, Grade::Good)?;
+        assert_eq!(session::next(&mut c, id)?, Some(a));
+        score(&mut c, a, Grade::Easy)?;
+        assert_eq!(session::next(&mut c, id)?, Some(d));
+
+        // undoing a failure takes the item out of the queue again
+        score(&mut c, d, Grade::Again)?;
+        assert_eq!(session::next(&mut c, id)?, Some(d));
+        review_log::undo(&mut c, None)?;
+        assert_eq!(session::next(&mut c, id)?, Some(d));
+        session::end(&mut c, id, now)?;
+        assert_eq!(session::open(&c)?, None);
+
+        let summary = session::summary(&mut c, id)?;
+        assert_eq!((summary.n_reviews, summary.n_remaining), (3, 1));
+        let grades = session::Grades {
+            again: 1,
+            hard: 0,
+            good: 1,
+            easy: 1,
+        };
+        assert_eq!(summary.grades, grades);
+        assert_eq!(summary.response_time_ms, Some(3000));
+        // every item was new
+        assert_eq!(summary.retention, None);
+        Ok(())
+    }
+    #[test]
+    fn test_set_card_state() -> Result<()> {
+        use time::Duration;
+        let mut c = init()?;
+        let id = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]).unwrap();
+        let now = OffsetDateTime::now_utc();
+        let state = sra::CardState {
+            maturity: sra::model::Maturity::Young,
+            s: 3.0,
+            d: 5.0,
+            last_review: now,
+            reps: 4,
+            lapses: 1,
+            step: 0,
+        };
+        review::set_card_state(&c, id, &state, now + Duration::DAY)?;
+        let item = item::get(&mut c, id)?;
+        assert_eq!(item.maturity, Maturity::Young);
+        assert_eq!((item.n_reviews, item.n_lapses), (4, 1));
+        assert_eq!(item.card_state(), state);
+        assert!(item.due_date.is_some());
+        Ok(())
+    }
+    // -------------
+    // ==== review log ====
+    #[test]
+    fn test_add_and_query_review_log() -> Result<()> {
+        use sra::model::{Grade, ModelData};
+        let mut c = init()?;
+        let id1 = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]).unwrap();
+        let id2 = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[]).unwrap();
+        let now = OffsetDateTime::now_utc();
+        let after = || ModelData { s: 3.0, d: 5.0 };
+        let (item1, item2) = (item::get(&mut c, id1)?, item::get(&mut c, id2)?);
+        review_log::add(&c, Grade::Good, now, 0.0, &item1, after(), Some(1500)).unwrap();
+        review_log::add(&c, Grade::Again, now, 1.5, &item2, after(), None).unwrap();
+
+        let logs = review_log::query(&mut c, None).unwrap();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].grade, Grade::Good);
+        assert_eq!(logs[0].duration_ms, Some(1500));
+        assert_eq!(logs[1].elapsed_days, 1.5);
+
+        let filter = filter_language::FilterLangParser::parse(&format!("item_id == {id2}"))?;
+        let logs = review_log::query(&mut c, Some(filter)).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].grade, Grade::Again);
+        assert_eq!(logs[0].stability_after, 3.0);
+
+        // the history is removed together with the item
+        item::delete(&mut c, id1).unwrap();
+        assert_eq!(review_log::query(&mut c, None).unwrap().len(), 1);
+        Ok(())
+    }
+    #[test]
+    fn test_undo_review() -> Result<()> {
+        use sra::model::{Grade, Maturity::*, ModelData};
+        use time::Duration;
+        let mut c = init()?;
+        let id1 = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[])?;
+        let id2 = item::add(&mut c, "flashcard", r#"{"front":"foo","back":"bar"}"#, &[])?;
+        let now = OffsetDateTime::now_utc();
+        let young = sra::CardState {
+            maturity: Young,
+            s: 10.0,
+            d: 5.0,
+            last_review: now - Duration::days(10),
+            reps: 3,
+            lapses: 1,
+            step: 0,
+        };
+        review::set_card_state(&c, id1, &young, now)?;
+        // score item 1 with again and then item 2
+        let score = |c: &mut Connection, id: i32, next: &sra::CardState| -> Result<i32> {
+            let before = item::get(c, id)?;
+            review::set_card_state(c, id, next, now + Duration::MINUTE)?;
+            let after = ModelData {
+                s: next.s,
+                d: next.d,
+            };
+            review_log::add(c, Grade::Again, now, 10.0, &before, after, None)
+        };
+        let relearning = sra::CardState {
+            maturity: Relearning,
+            s: 2.0,
+            last_review: now,
+            reps: 4,
+            lapses: 2,
+            ..young
+        };
+        // the review of item 1 made it a leech
+        let log = score(&mut c, id1, &relearning)?;
+        assert_eq!(item::add_tags(&c, id1, &["leech"])?, 1);
+        review_log::set_leech_tag(&c, log, "leech")?;
+        score(&mut c, id2, &relearning)?;
+
+        assert_eq!(review_log::undo(&mut c, Some(id1))?, Some(id1));
+        assert!(item::get_tags(&mut c, id1)?.is_empty());
+        let item = item::get(&mut c, id1)?;
+        assert_eq!(item.card_state(), young);
+        assert_eq!(item.due_date, Some(now));
+        assert_eq!(review_log::query(&mut c, None)?.len(), 1);
+        // the most recent review of any item
+        assert_eq!(review_log::undo(&mut c, None)?, Some(id2));
+        assert_eq!(item::get(&mut c, id2)?.maturity, Maturity::New);
+        assert_eq!(review_log::undo(&mut c, None)?, None);
+        Ok(())
+    }
+    // -------------
 }
 
 pub mod utils {
@@ -573,9 +1648,22 @@ pub mod utils {
                 // when dealing with fields that describe time, we require that all values
                 // constitute valid time formats
                 // <https://www.sqlite.org/lang_datefunc.html>
-                "updated_at" | "created_at" | "last_review_date" => format!(
-                    "datetime({}) {} datetime({})",
-                    column,
+                "updated_at" | "created_at" | "last_review_date" | "review_date" | "due_date" => {
+                    format!(
+                        "datetime({}) {} datetime({})",
+                        column,
+                        op,
+                        filter_expr_to_sql(value)
+                    )
+                }
+                // computed by the sra functions registered on the db
+                "retrievability" => format!(
+                    "retrievability(stability, last_review_date) {} {}",
+                    op,
+                    filter_expr_to_sql(value)
+                ),
+                "elapsed_days" => format!(
+                    "elapsed_days(last_review_date) {} {}",
                     op,
                     filter_expr_to_sql(value)
                 ),
@@ -1,4 +1,3 @@
-use rusqlite;
 use rusqlite::types::FromSql;
 use rusqlite::types::FromSqlError;
 use rusqlite::ToSql;
@@ -30,23 +29,29 @@ pub type ItemData = JsonData;
 pub type TagName = String;
 
 /// A measure of how well we've 'learnt' an item.
-#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Maturity {
     /// This item has not yet been reviewed
     #[default]
     New,
+    /// This item is going through its learning steps
+    Learning,
     /// This item has been reviewed but has a stability less than 100 days.
     Young,
     /// This items has been reviewed many times and can probably be considered fully 'learnt'
     Tenured,
+    /// This item was forgotten and is going through its relearning steps
+    Relearning,
 }
 impl FromSql for Maturity {
     fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
         let s = value.as_str()?;
         match s {
             "new" => Ok(Maturity::New),
+            "learning" => Ok(Maturity::Learning),
             "young" => Ok(Maturity::Young),
             "tenured" => Ok(Maturity::Tenured),
+            "relearning" => Ok(Maturity::Relearning),
             _ => Err(FromSqlError::InvalidType),
         }
     }
@@ -56,18 +61,46 @@ impl ToSql for Maturity {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
         Ok(match self {
             Maturity::New => "new".into(),
+            Maturity::Learning => "learning".into(),
             Maturity::Young => "young".into(),
             Maturity::Tenured => "tenured".into(),
+            Maturity::Relearning => "relearning".into(),
         })
     }
 }
 
+impl From<Maturity> for sra::model::Maturity {
+    fn from(m: Maturity) -> Self {
+        match m {
+            Maturity::New => Self::New,
+            Maturity::Learning => Self::Learning,
+            Maturity::Young => Self::Young,
+            Maturity::Tenured => Self::Tenured,
+            Maturity::Relearning => Self::Relearning,
+        }
+    }
+}
+
+impl From<sra::model::Maturity> for Maturity {
+    fn from(m: sra::model::Maturity) -> Self {
+        match m {
+            sra::model::Maturity::New => Self::New,
+            sra::model::Maturity::Learning => Self::Learning,
+            sra::model::Maturity::Young => Self::Young,
+            sra::model::Maturity::Tenured => Self::Tenured,
+            sra::model::Maturity::Relearning => Self::Relearning,
+        }
+    }
+}
+
 impl std::fmt::Display for Maturity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Maturity::New => write!(f, "New"),
+            Maturity::Learning => write!(f, "Learning"),
             Maturity::Young => write!(f, "Young"),
             Maturity::Tenured => write!(f, "Tenured"),
+            Maturity::Relearning => write!(f, "Relearning"),
         }
     }
 }
@@ -88,7 +121,34 @@ pub struct Item {
     pub updated_at: OffsetDateTime,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
+    /// When the item should be reviewed again. `None` for new items.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub due_date: Option<OffsetDateTime>,
+    /// The current step of an item that is (re)learning
+    pub learning_step: i32,
+    /// Whether the item is left out of reviews
+    pub suspended: bool,
+    /// Until when the item is left out of reviews. `None` if it is not buried.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub buried_until: Option<OffsetDateTime>,
+    /// Items with the same group are siblings, e.g. made from the same source
+    pub sibling_group: Option<String>,
 }
+impl Item {
+    /// The part of the item that the scheduler looks at
+    pub fn card_state(&self) -> sra::CardState {
+        sra::CardState {
+            maturity: self.maturity.into(),
+            s: self.stability,
+            d: self.difficulty,
+            last_review: self.last_review_date,
+            reps: self.n_reviews.max(0) as u32,
+            lapses: self.n_lapses.max(0) as u32,
+            step: self.learning_step.max(0) as u32,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Tag {
     pub id: i32,
@@ -98,3 +158,23 @@ pub struct Tag {
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
 }
+
+/// A single scored review of an item, together with the sra parameters
+/// before and after the review was applied.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReviewLog {
+    pub id: i32,
+    pub item_id: i32,
+    pub grade: sra::model::Grade,
+    #[serde(with = "time::serde::rfc3339")]
+    pub review_date: OffsetDateTime,
+    pub elapsed_days: sra::model::Time,
+    pub stability_before: sra::model::Stability,
+    pub difficulty_before: sra::model::Difficulty,
+    pub stability_after: sra::model::Stability,
+    pub difficulty_after: sra::model::Difficulty,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// How long the recall took in milliseconds, if known
+    pub duration_ms: Option<u32>,
+}
@@ -0,0 +1,106 @@
+//! Study days. Reviews are planned per day, but a day need not begin at midnight UTC. It
+//! begins at some hour in some time zone instead, e.g. at 4am in Asia/Tokyo, such that a
+//! late night review still counts towards the day before.
+
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time};
+use time_tz::{timezones, OffsetDateTimeExt, PrimitiveDateTimeExt, Tz};
+
+/// When days begin
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StudyDay {
+    tz: &'static Tz,
+    /// the hour of the day at which days begin
+    starts_at: u8,
+}
+
+impl Default for StudyDay {
+    /// days begin at midnight UTC
+    fn default() -> Self {
+        Self {
+            tz: timezones::db::etc::UTC,
+            starts_at: 0,
+        }
+    }
+}
+
+impl StudyDay {
+    /// Days beginning at `starts_at` o'clock in the time zone named `timezone`, e.g.
+    /// "Europe/Stockholm". `None` if there is no such time zone or hour.
+    pub fn new(timezone: &str, starts_at: u8) -> Option<Self> {
+        let tz = timezones::get_by_name(timezone)?;
+        (starts_at < 24).then_some(Self { tz, starts_at })
+    }
+
+    /// The day that `t` falls on
+    pub fn date(&self, t: OffsetDateTime) -> Date {
+        (t.to_timezone(self.tz) - Duration::hours(self.starts_at as i64)).date()
+    }
+
+    /// The moment `date` begins
+    pub fn start(&self, date: Date) -> OffsetDateTime {
+        let start =
+            PrimitiveDateTime::new(date, Time::MIDNIGHT) + Duration::hours(self.starts_at as i64);
+        // a start that is skipped by a change to daylight saving time is off by the change
+        start
+            .assume_timezone(self.tz)
+            .take_first()
+            .unwrap_or_else(|| start.assume_timezone_utc(self.tz))
+    }
+
+    /// The moment the day after the day of `t` begins
+    pub fn next(&self, t: OffsetDateTime) -> OffsetDateTime {
+        self.start(self.date(t) + Duration::DAY)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn days_begin_at_the_hour_in_the_time_zone() {
+        let utc = StudyDay::default();
+        assert_eq!(
+            utc.date(datetime!(2024-01-01 23:30 UTC)),
+            date!(2024 - 01 - 01)
+        );
+        assert_eq!(
+            utc.start(date!(2024 - 01 - 02)),
+            datetime!(2024-01-02 0:00 UTC)
+        );
+
+        // 4am in Tokyo is 19:00 UTC the day before
+        let tokyo = StudyDay::new("Asia/Tokyo", 4).unwrap();
+        assert_eq!(
+            tokyo.date(datetime!(2024-01-01 18:59 UTC)),
+            date!(2024 - 01 - 01)
+        );
+        assert_eq!(
+            tokyo.date(datetime!(2024-01-01 19:00 UTC)),
+            date!(2024 - 01 - 02)
+        );
+        assert_eq!(
+            tokyo.start(date!(2024 - 01 - 02)),
+            datetime!(2024-01-01 19:00 UTC)
+        );
+        assert_eq!(
+            tokyo.next(datetime!(2024-01-01 12:00 UTC)),
+            datetime!(2024-01-01 19:00 UTC)
+        );
+
+        // the offset follows daylight saving time
+        let stockholm = StudyDay::new("Europe/Stockholm", 0).unwrap();
+        assert_eq!(
+            stockholm.start(date!(2024 - 01 - 01)),
+            datetime!(2023-12-31 23:00 UTC)
+        );
+        assert_eq!(
+            stockholm.start(date!(2024 - 07 - 01)),
+            datetime!(2024-06-30 22:00 UTC)
+        );
+
+        assert_eq!(StudyDay::new("Mars/Olympus_Mons", 0), None);
+        assert_eq!(StudyDay::new("UTC", 24), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Workload forecasts, projecting the number of reviews on each of the coming days.
+//!
+//! Every item is reviewed whenever it comes due, and rescheduled with [`schedule`], until
+//! the forecast ends. Either every review is graded Good, or the reviews are simulated:
+//! an item is recalled (Good) with the probability [`r`] of its memory state and
+//! forgotten (Again) otherwise, averaged over a number of runs. Intervals are not fuzzed.
+//! Reviews are counted per [`StudyDay`].
+
+use super::*;
+use day::StudyDay;
+use scheduler::Settings;
+use time::{Duration, OffsetDateTime};
+
+/// An item, and how it is scheduled
+pub struct Card<'a> {
+    pub state: CardState,
+    /// when the item is due, overdue items are reviewed right away
+    pub due: OffsetDateTime,
+    pub scheduler: &'a dyn Scheduler,
+    pub settings: &'a Settings,
+}
+
+/// Items that are yet to be learnt, `per_day` of them at the start of each day
+pub struct NewCards<'a> {
+    pub per_day: u32,
+    pub scheduler: &'a dyn Scheduler,
+    pub settings: &'a Settings,
+}
+
+/// How the reviews of the forecast are graded
+pub enum Grading {
+    /// every review is graded Good
+    Good,
+    /// recall is sampled from the memory model `p`. The seed makes the forecast
+    /// repeatable, see [`fuzz::factor`].
+    Simulated { p: Parameters, runs: u32, seed: u64 },
+}
+
+/// The (mean) number of reviews on each of the `days` days starting with the day of `now`.
+/// The last review of a day may come due the next day in (re)learning items, which is
+/// counted on the day that it is reviewed.
+pub fn forecast(
+    cards: &[Card],
+    new: Option<&NewCards>,
+    now: OffsetDateTime,
+    days: u32,
+    study_day: &StudyDay,
+    grading: &Grading,
+) -> Vec<f32> {
+    let today = study_day.date(now);
+    let end = study_day.start(today + Duration::days(days as i64));
+    let runs = match grading {
+        Grading::Good => 1,
+        Grading::Simulated { runs, .. } => (*runs).max(1),
+    };
+    let mut counts = vec![0u32; days as usize];
+
+    for run in 0..runs {
+        // an id per item such that each one is graded by its own random numbers
+        let mut id = 0u64;
+        let mut review = |scheduler: &dyn Scheduler,
+                          settings: &Settings,
+                          mut state: CardState,
+                          mut due: OffsetDateTime| {
+            id += 1;
+            while due < end {
+                counts[(study_day.date(due) - today).whole_days() as usize] += 1;
+                let g = grade(grading, run, id, &state, due);
+                (state, due) = schedule(scheduler, settings, &state, g, due, None, None);
+            }
+        };
+        for card in cards {
+            review(card.scheduler, card.settings, card.state, card.due.max(now));
+        }
+        let Some(new) = new else {
+            continue;
+        };
+        for day in 0..days {
+            let start = study_day.start(today + Duration::days(day as i64)).max(now);
+            for _ in 0..new.per_day {
+                review(new.scheduler, new.settings, CardState::new(start), start);
+            }
+        }
+    }
+    counts.iter().map(|n| *n as f32 / runs as f32).collect()
+}
+
+/// The grade of the review of item `id` in `state` at `now`
+fn grade(grading: &Grading, run: u32, id: u64, state: &CardState, now: OffsetDateTime) -> Grade {
+    let Grading::Simulated { p, seed, .. } = grading else {
+        return Grade::Good;
+    };
+    let Some(m) = state.memory() else {
+        // the first review of an item is not a question of recall
+        return Grade::Good;
+    };
+    let recall = r(p, state.elapsed_days(now), m.s);
+    let x = fuzz::factor(seed.wrapping_add(run as u64), id, state.reps);
+    if x < recall {
+        Grade::Good
+    } else {
+        Grade::Again
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scheduler::Fsrs;
+    use time::macros::datetime;
+
+    #[test]
+    fn forecast_reviews() {
+        let now = datetime!(2024-01-01 12:00 UTC);
+        let scheduler = Fsrs {
+            p: Parameters::default(),
+            desired_retention: 0.9,
+        };
+        let settings = Settings::default();
+        let utc = StudyDay::default();
+        let card = |due| Card {
+            state: CardState {
+                maturity: model::Maturity::Young,
+                s: 10.0,
+                d: 5.0,
+                last_review: now - Duration::days(10),
+                reps: 3,
+                lapses: 0,
+                step: 0,
+            },
+            due,
+            scheduler: &scheduler,
+            settings: &settings,
+        };
+        // overdue items are reviewed today, and not again within the week
+        let cards = [card(now - Duration::days(2)), card(now + Duration::days(3))];
+        let reviews = forecast(&cards, None, now, 7, &utc, &Grading::Good);
+        assert_eq!(reviews, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+
+        // new items are shown, and shown again after the second step, on the day they are
+        // learnt
+        let new = NewCards {
+            per_day: 2,
+            scheduler: &scheduler,
+            settings: &Settings {
+                steps: Steps {
+                    learning: vec!["1m".parse().unwrap(), "10m".parse().unwrap()],
+                    relearning: vec![],
+                },
+                ..Settings::default()
+            },
+        };
+        let reviews = forecast(&[], Some(&new), now, 2, &utc, &Grading::Good);
+        assert_eq!(reviews[0], 4.0);
+
+        // forgetting adds reviews, and simulations are repeatable
+        let simulated = Grading::Simulated {
+            p: Parameters::default(),
+            runs: 50,
+            seed: 1,
+        };
+        let good = forecast(&[], Some(&new), now, 30, &utc, &Grading::Good);
+        let sampled = forecast(&[], Some(&new), now, 30, &utc, &simulated);
+        assert!(sampled.iter().sum::<f32>() > good.iter().sum::<f32>());
+        assert_eq!(
+            sampled,
+            forecast(&[], Some(&new), now, 30, &utc, &simulated)
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Interval fuzz, spreading out the due dates of items that were learnt together.
+//!
+//! Only the interval is fuzzed, the memory state is left as is. Longer intervals are
+//! fuzzed by more days, using the same ranges as Anki. The fuzz is chosen by a factor in
+//! `[0, 1)`, which [`factor`] derives deterministically from a seed and the item.
+
+use super::*;
+
+struct FuzzRange {
+    start: Interval,
+    end: Interval,
+    factor: f32,
+}
+
+const FUZZ_RANGES: [FuzzRange; 3] = [
+    FuzzRange {
+        start: 2.5,
+        end: 7.0,
+        factor: 0.15,
+    },
+    FuzzRange {
+        start: 7.0,
+        end: 20.0,
+        factor: 0.1,
+    },
+    FuzzRange {
+        start: 20.0,
+        end: f32::INFINITY,
+        factor: 0.05,
+    },
+];
+
+/// Intervals shorter than this are not fuzzed
+pub const MIN_FUZZ_INTERVAL: Interval = 2.5;
+
+/// The whole number of days, inclusive, that `interval` may be fuzzed to. An item reviewed
+/// `elapsed` days after its previous review is not scheduled sooner than it was this time.
+pub fn range(interval: Interval, elapsed: Time) -> (Interval, Interval) {
+    let delta = 1.0
+        + FUZZ_RANGES
+            .iter()
+            .map(|r| r.factor * (interval.min(r.end) - r.start).max(0.0))
+            .sum::<f32>();
+    let mut lo = (interval - delta).round().max(2.0);
+    let hi = (interval + delta).round();
+    if interval > elapsed {
+        lo = lo.max(elapsed.floor() + 1.0);
+    }
+    (lo.min(hi), hi)
+}
+
+/// `interval` fuzzed to a day within [`range`], chosen by `factor` in `[0, 1)`
+pub fn apply(interval: Interval, elapsed: Time, factor: f32) -> Interval {
+    if interval < MIN_FUZZ_INTERVAL {
+        return interval;
+    }
+    let (lo, hi) = range(interval, elapsed);
+    (factor * (hi - lo + 1.0) + lo).floor().min(hi)
+}
+
+/// A factor in `[0, 1)` that only depends on its arguments, such that the fuzz of an item
+/// is the same every time a review is previewed or replayed
+pub fn factor(seed: u64, id: u64, reps: u32) -> f32 {
+    // splitmix64
+    let mut z = seed
+        .wrapping_add(id.wrapping_mul(0x9E3779B97F4A7C15))
+        .wrapping_add((reps as u64).wrapping_mul(0xD1B54A32D192ED03));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^= z >> 31;
+    // the 24 most significant bits fit in the mantissa of a f32
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fuzz_range_grows_with_interval() {
+        assert_eq!(range(3.0, 0.0), (2.0, 4.0));
+        assert_eq!(range(10.0, 0.0), (8.0, 12.0));
+        assert_eq!(range(100.0, 0.0), (93.0, 107.0));
+        // not sooner than the interval that just passed
+        assert_eq!(range(10.0, 9.0), (10.0, 12.0));
+        // short intervals are kept
+        assert_eq!(apply(1.5, 0.0, 0.9), 1.5);
+    }
+
+    #[test]
+    fn fuzz_covers_the_range() {
+        let fuzzed: Vec<Interval> = [0.0, 0.2, 0.4, 0.6, 0.8, 0.999]
+            .map(|f| apply(10.0, 0.0, f))
+            .to_vec();
+        assert_eq!(fuzzed, [8.0, 9.0, 10.0, 11.0, 12.0, 12.0]);
+    }
+
+    #[test]
+    fn factor_is_deterministic() {
+        assert_eq!(factor(0, 1, 2), factor(0, 1, 2));
+        assert_ne!(factor(0, 1, 2), factor(0, 1, 3));
+        assert_ne!(factor(0, 1, 2), factor(1, 1, 2));
+        let factors: Vec<f32> = (0..1000).map(|id| factor(7, id, 0)).collect();
+        assert!(factors.iter().all(|f| (0.0..1.0).contains(f)));
+        let mean = factors.iter().sum::<f32>() / factors.len() as f32;
+        assert!((mean - 0.5).abs() < 0.05);
+    }
+}
@@ -1,5 +1,5 @@
-//! This library contains a simpler implementation of the FSRS algorithm developed by Jarrett Ye.
-//! TODO update to https://expertium.github.io/Algorithm.html
+//! This library contains an implementation of the FSRS-6 algorithm developed by Jarrett Ye.
+//! See <https://expertium.github.io/Algorithm.html> for a description of the formulas.
 
 pub mod model {
     pub(crate) mod grade_ops;
@@ -18,12 +18,29 @@ pub mod model {
     pub type Interval = f32;
 
     /// Each review prompt has some parameters that we use to schedule it
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Clone, Copy, Serialize, Deserialize, Debug)]
     pub struct ModelData {
         pub s: Stability,
         pub d: Difficulty,
     }
 
+    /// A measure of how well an item has been learnt
+    #[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+    pub enum Maturity {
+        /// never reviewed
+        #[default]
+        New,
+        /// going through the learning steps, see [`crate::steps`]
+        Learning,
+        /// reviewed, with a stability of at most [`crate::scheduler::TENURED_STABILITY`]
+        Young,
+        /// has reached a stability above [`crate::scheduler::TENURED_STABILITY`] and has not
+        /// been forgotten since
+        Tenured,
+        /// forgotten, going through the relearning steps
+        Relearning,
+    }
+
     /// When reviewing an item using its associated prompt
     /// we need grade how well we could satisfy the prompt
     /// 1 -> Again, user could not recall
@@ -41,30 +58,81 @@ pub mod model {
 use model::*;
 use Grade::*;
 
-pub const ALGORITHM_VERSION: usize = 5;
+pub const ALGORITHM_VERSION: usize = 6;
+
+pub mod day;
+pub mod forecast;
+pub mod fuzz;
+pub mod load_balance;
+pub mod optimizer;
+pub mod parameters;
+pub mod scheduler;
+pub mod steps;
+
+pub use parameters::{Parameters, Weights};
+pub use scheduler::{schedule, CardState, Scheduler};
+pub use steps::{Step, Steps};
 
-/// Weights. Current best weights for the FSRS algorithm
-const W: [f32; 19] = [
-    0.40255, 1.18385, 3.173, 15.69105, 7.1949, 0.5345, 1.4604, 0.0046, 1.54575, 0.1192, 1.01925,
-    1.9395, 0.11, 0.29605, 2.2698, 0.2315, 2.9898, 0.51655, 0.6621,
+/// Weights. Current best weights for the FSRS algorithm. The last weight is the decay
+/// of the forgetting curve.
+pub const W: Weights = [
+    0.212, 1.2931, 2.3065, 8.2956, 6.4133, 0.8334, 3.0194, 0.001, 1.8722, 0.1666, 0.796, 1.4835,
+    0.0614, 0.2629, 1.6483, 0.6014, 1.8729, 0.5425, 0.0912, 0.0658, 0.1542,
 ];
 
-/// factor
-const F: f32 = 19.0 / 81.0;
-/// decay
-const D: f32 = -0.5;
+/// The lowest stability a memory may have
+pub const S_MIN: Stability = 0.001;
+/// The highest stability a memory may have
+pub const S_MAX: Stability = 36500.0;
+/// The lowest difficulty a memory may have
+pub const D_MIN: Difficulty = 1.0;
+/// The highest difficulty a memory may have
+pub const D_MAX: Difficulty = 10.0;
 
 use model::{Difficulty, Retrievability, Stability, Time};
 
 /// retrievability - the probability after t days that prompt will be satisfied
-pub fn r(t: Time, s: Stability) -> Retrievability {
-    (1.0 + F * (t / s)).powf(D)
+pub fn r(p: &Parameters, t: Time, s: Stability) -> Retrievability {
+    (1.0 + p.factor() * (t / s)).powf(p.decay())
 }
 
-/// interval - the amount of days until retrievability reaches [`r`], rounded away from zero
+/// interval - the amount of days until retrievability reaches [`r`]
 /// `i(0.9, s) == s`
-pub fn i(r: Retrievability, s: Stability) -> Interval {
-    s / F * (r.powf(1.0 / D) - 1.0)
+pub fn i(p: &Parameters, r: Retrievability, s: Stability) -> Interval {
+    s / p.factor() * (r.powf(1.0 / p.decay()) - 1.0)
+}
+
+/// The memory state after a review with grade `g`, `elapsed` days after the previous one.
+/// `state` is `None` for the very first review of an item. Reviews on the same study day
+/// as the previous one (`same_day`) are handled as short term reviews.
+pub fn next_state(
+    p: &Parameters,
+    state: Option<ModelData>,
+    g: Grade,
+    elapsed: Time,
+    same_day: bool,
+) -> ModelData {
+    let Some(ModelData { s, d }) = state else {
+        return ModelData {
+            s: init::s(p, g).clamp(S_MIN, S_MAX),
+            d: init::d(p, g).clamp(D_MIN, D_MAX),
+        };
+    };
+    let s = s.clamp(S_MIN, S_MAX);
+    let d = d.clamp(D_MIN, D_MAX);
+    let next_s = if same_day {
+        update::shortterm::s(p, s, g)
+    } else {
+        let r = r(p, elapsed, s);
+        match g {
+            Again => update::fail::s(p, s, d, r),
+            g => update::success::s(p, s, d, r, g),
+        }
+    };
+    ModelData {
+        s: next_s.clamp(S_MIN, S_MAX),
+        d: update::d(p, d, g),
+    }
 }
 
 pub mod update {
@@ -73,31 +141,42 @@ pub mod update {
     pub mod success {
         use super::*;
 
-        pub fn s(s: Stability, d: Difficulty, r: Retrievability, g: Grade) -> Stability {
+        /// Stability after a successful long term review
+        pub fn s(
+            p: &Parameters,
+            s: Stability,
+            d: Difficulty,
+            r: Retrievability,
+            g: Grade,
+        ) -> Stability {
             assert!(g != Grade::Again); // this function should not be called for a failing grade
+            let w = &p.w;
             let scaling: f32 = {
                 let (w_15, w_16) = match g {
                     Again => unreachable!(),
-                    Hard => (W[15], 1.0),
+                    Hard => (w[15], 1.0),
                     Good => (1.0, 1.0),
-                    Easy => (1.0, W[16]),
+                    Easy => (1.0, w[16]),
                 };
-                w_15 * w_16 * W[8].exp()
+                w_15 * w_16 * w[8].exp()
             };
             let f_d: f32 = 11.0 - d;
-            let f_s: f32 = s.powf(-W[9]);
-            let f_r: f32 = (W[10] * (1.0 - r)).exp() - 1.0;
+            let f_s: f32 = s.powf(-w[9]);
+            let f_r: f32 = (w[10] * (1.0 - r)).exp() - 1.0;
             let s_inc = 1.0 + scaling * f_d * f_s * f_r;
-            s.min(s * s_inc)
+            s * s_inc
         }
     }
 
     pub mod fail {
         use super::*;
-        pub fn s(s: Stability, d: Difficulty, r: Retrievability) -> Stability {
-            s.min(
-                W[11] * d.powf(-W[12]) * ((s + 1.0).powf(W[13]) - 1.0) * (W[14] * (1.0 - r)).exp(),
-            )
+        /// Stability after a failed long term review (a lapse). Never exceeds the stability
+        /// that the same-day review rule would give.
+        pub fn s(p: &Parameters, s: Stability, d: Difficulty, r: Retrievability) -> Stability {
+            let w = &p.w;
+            let post_lapse_s =
+                w[11] * d.powf(-w[12]) * ((s + 1.0).powf(w[13]) - 1.0) * (w[14] * (1.0 - r)).exp();
+            post_lapse_s.min(s / (w[17] * w[18]).exp())
         }
     }
 
@@ -106,18 +185,30 @@ pub mod update {
     /// - Hard -> increase a little bit
     /// - Good -> nothing
     /// - Easy -> subtract
+    ///
+    /// The change is damped linearly as the difficulty approaches 10, after which the
+    /// difficulty reverts slightly towards `init::d(Easy)`.
+    ///
     /// NOTE: does not take retrievability into account.
-    pub fn d(d: Difficulty, g: Grade) -> Difficulty {
-        let delta_d = -W[6] * (g - 3.0); // change in terms of grade
-        let mean_revision = W[7] * init::d(Hard) + (1.0 - W[7]); // bias the difficulty in the direction of a d==init:d(Hard)
-        mean_revision * (d + delta_d * (10.0 - D) / 9.0).clamp(0.0, 10.0) // approach
+    pub fn d(p: &Parameters, d: Difficulty, g: Grade) -> Difficulty {
+        let w = &p.w;
+        let delta_d = -w[6] * (g - 3.0); // change in terms of grade
+        let d = d + delta_d * (10.0 - d) / 9.0; // linear damping
+        let d = w[7] * init::d(p, Easy) + (1.0 - w[7]) * d; // mean reversion
+        d.clamp(D_MIN, D_MAX)
     }
 
     pub mod shortterm {
         use super::*;
-        /// Stability update rule for short term reviews
-        pub fn s(s: Stability, g: Grade) -> Stability {
-            s * (W[17] * (g - 3.0 + W[18])).exp()
+        /// Stability update rule for short term (same-day) reviews. Good and Easy never
+        /// decrease the stability.
+        pub fn s(p: &Parameters, s: Stability, g: Grade) -> Stability {
+            let w = &p.w;
+            let s_inc = (w[17] * (g - 3.0 + w[18])).exp() * s.powf(-w[19]);
+            match g {
+                Good | Easy => s * s_inc.max(1.0),
+                Again | Hard => s * s_inc,
+            }
         }
     }
 }
@@ -126,12 +217,117 @@ pub mod init {
     use super::*;
 
     /// Initial stability
-    pub fn s(g: Grade) -> Stability {
-        W[g - 1]
+    pub fn s(p: &Parameters, g: Grade) -> Stability {
+        p.w[g - 1]
+    }
+
+    /// Initial difficulty. Not clamped, as the mean reversion of [`update::d`] is done
+    /// towards the unclamped `d(Easy)`. See [`next_state`].
+    pub fn d(p: &Parameters, g: Grade) -> Difficulty {
+        p.w[4] - (p.w[5] * (g - 1.0)).exp() + 1.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    //! Reference values from the published FSRS-6 implementation <https://github.com/open-spaced-repetition/fsrs-rs>
+    use super::*;
+
+    fn assert_approx_eq(actual: &[f32], expected: &[f32]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
+        }
     }
 
-    /// Initial difficulty
-    pub fn d(g: Grade) -> Difficulty {
-        W[4] - (W[5] * (g - 1.0)).exp() + 1.0
+    const GRADES: [Grade; 4] = [Again, Hard, Good, Easy];
+
+    #[test]
+    fn forgetting_curve() {
+        let p = Parameters::default();
+        let r: Vec<f32> = [
+            (0.0, 1.0),
+            (1.0, 2.0),
+            (2.0, 3.0),
+            (3.0, 4.0),
+            (4.0, 4.0),
+            (5.0, 2.0),
+        ]
+        .into_iter()
+        .map(|(t, s)| r(&p, t, s))
+        .collect();
+        assert_approx_eq(&r, &[1.0, 0.9403443, 0.9253786, 0.9185229, 0.9, 0.8261359]);
+    }
+
+    #[test]
+    fn interval() {
+        let p = Parameters::default();
+        let intervals: Vec<i32> = (1..=10)
+            .map(|r| i(&p, r as f32 / 10.0, 1.0).round().max(1.0) as i32)
+            .collect();
+        assert_eq!(intervals, [3116766, 34793, 2508, 387, 90, 27, 9, 3, 1, 1]);
+        assert!((i(&p, 0.9, 121.01552) - 121.01552).abs() < 1e-3);
+    }
+
+    #[test]
+    fn initial_state() {
+        let p = Parameters::default();
+        let s = GRADES.map(|g| init::s(&p, g));
+        assert_eq!(s, [W[0], W[1], W[2], W[3]]);
+        let states = GRADES.map(|g| next_state(&p, None, g, 0.0, true));
+        let d: Vec<f32> = states.iter().map(|m| m.d).collect();
+        assert_approx_eq(&d, &[6.4133, 5.1121707, 2.118104, 1.0]);
+    }
+
+    #[test]
+    fn difficulty() {
+        let p = Parameters::default();
+        let d = GRADES.map(|g| update::d(&p, 5.0, g));
+        assert_approx_eq(&d, &[8.341763, 6.6659956, 4.990228, 3.3144615]);
+    }
+
+    #[test]
+    fn stability() {
+        let p = Parameters::default();
+        let s_recall = [(Hard, 2.0, 0.8), (Good, 3.0, 0.7), (Easy, 4.0, 0.6)]
+            .map(|(g, d, r)| update::success::s(&p, 5.0, d, r, g));
+        assert_approx_eq(&s_recall, &[28.226582, 58.656002, 127.226685]);
+
+        let s_forget = [(1.0, 0.9), (2.0, 0.8), (3.0, 0.7), (4.0, 0.6)]
+            .map(|(d, r)| update::fail::s(&p, 5.0, d, r));
+        assert_approx_eq(&s_forget, &[1.0525396, 1.1894329, 1.3680838, 1.584989]);
+
+        let s_short = GRADES.map(|g| update::shortterm::s(&p, 5.0, g));
+        assert_approx_eq(&s_short, &[1.596818, 2.7470096, 5.0, 8.12961]);
+    }
+
+    fn memory_state(p: &Parameters) -> ModelData {
+        let reviews = [
+            (Again, 0.0),
+            (Good, 0.0),
+            (Good, 1.0),
+            (Good, 3.0),
+            (Good, 8.0),
+            (Good, 21.0),
+        ];
+        reviews
+            .into_iter()
+            .fold(None, |state, (g, t)| {
+                Some(next_state(p, state, g, t, t < 1.0))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn review_sequence() {
+        let mut p = Parameters::default();
+        let m = memory_state(&p);
+        assert_approx_eq(&[m.s, m.d], &[53.62691, 6.3574867]);
+
+        // without same-day reviews affecting the stability
+        p.w[17] = 0.0;
+        p.w[18] = 0.0;
+        p.w[19] = 0.0;
+        let m = memory_state(&p);
+        assert_approx_eq(&[m.s, m.d], &[53.335106, 6.3574867]);
     }
 }
@@ -0,0 +1,86 @@
+//! Load balancing, moving an interval within its fuzz range to the day with the fewest
+//! reviews. Items that are learnt together are spread out instead of all coming due on
+//! the same days.
+
+use super::*;
+use scheduler::Settings;
+use time::{Date, OffsetDateTime};
+
+/// The relative amount of reviews wanted on each weekday, Monday first. Days below 1 are
+/// easy days, which get proportionally fewer reviews. Days at 0 are avoided whenever
+/// possible.
+pub type WeekLoad = [f32; 7];
+
+/// Every day of the week gets the same amount of reviews
+pub const EVEN_WEEK: WeekLoad = [1.0; 7];
+
+/// The interval, a whole number of days within `lo..=hi`, whose due date has the fewest
+/// reviews relative to the load of its weekday in the `easy_days` of `settings`.
+/// `due_on(date)` is the number of items already due on the (study) day `date`. Ties are
+/// broken towards `target`, which is also returned when there is no day to choose from.
+pub fn balance(
+    now: OffsetDateTime,
+    lo: Interval,
+    hi: Interval,
+    target: Interval,
+    settings: &Settings,
+    due_on: &dyn Fn(Date) -> u32,
+) -> Interval {
+    let cost = |days: u32| {
+        let date = settings.day.date(now + time::Duration::days(days as i64));
+        let load = settings.easy_days[date.weekday().number_days_from_monday() as usize];
+        (due_on(date) + 1) as f32 / load
+    };
+    (lo.ceil() as u32..=hi.floor() as u32)
+        .map(|days| (cost(days), (days as f32 - target).abs(), days))
+        .filter(|(cost, _, _)| cost.is_finite())
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .map_or(target, |(_, _, days)| days as f32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use time::macros::{date, datetime};
+
+    #[test]
+    fn picks_the_least_loaded_day() {
+        // a monday
+        let now = datetime!(2024-01-01 12:00 UTC);
+        let day = |d: i64| (now + time::Duration::days(d)).date();
+        let due: BTreeMap<Date, u32> =
+            [(day(8), 5), (day(9), 2), (day(10), 2), (day(11), 7)].into();
+        let due_on = |date: Date| due.get(&date).copied().unwrap_or(0);
+        let even = Settings::default();
+        assert_eq!(balance(now, 8.0, 11.0, 9.0, &even, &due_on), 9.0);
+        assert_eq!(balance(now, 8.0, 11.0, 11.0, &even, &due_on), 10.0);
+        assert_eq!(balance(now, 8.0, 12.0, 9.0, &even, &due_on), 12.0);
+
+        // day 12 is a saturday and day 13 a sunday
+        let mut week = EVEN_WEEK;
+        week[5] = 0.25;
+        week[6] = 0.0;
+        let easy = Settings {
+            easy_days: week,
+            ..Settings::default()
+        };
+        assert_eq!(balance(now, 8.0, 12.0, 9.0, &easy, &due_on), 9.0);
+        assert_eq!(balance(now, 13.0, 13.0, 13.0, &easy, &due_on), 13.0);
+        assert_eq!(balance(now, 12.0, 13.0, 13.0, &easy, &due_on), 12.0);
+    }
+
+    #[test]
+    fn counts_study_days() {
+        // 20:00 UTC is the next morning in Tokyo
+        let now = datetime!(2024-01-01 20:00 UTC);
+        let due_on = |date: Date| if date == date!(2024 - 01 - 10) { 5 } else { 0 };
+        let utc = Settings::default();
+        assert_eq!(balance(now, 9.0, 10.0, 9.0, &utc, &due_on), 10.0);
+        let tokyo = Settings {
+            day: day::StudyDay::new("Asia/Tokyo", 4).unwrap(),
+            ..Settings::default()
+        };
+        assert_eq!(balance(now, 9.0, 10.0, 9.0, &tokyo, &due_on), 9.0);
+    }
+}
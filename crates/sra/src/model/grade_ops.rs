@@ -35,3 +35,19 @@ impl_grade_op!(Add, add, usize);
 impl_grade_op!(Mul, mul, usize);
 impl_grade_op!(Sub, sub, usize);
 impl_grade_op!(Div, div, usize);
+
+impl TryFrom<u8> for Grade {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Grade::Again),
+            2 => Ok(Grade::Hard),
+            3 => Ok(Grade::Good),
+            4 => Ok(Grade::Easy),
+            v => Err(format!(
+                "{v} is not a valid grade, expected a value in 1..=4"
+            )),
+        }
+    }
+}
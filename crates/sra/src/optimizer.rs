@@ -0,0 +1,200 @@
+//! Fit the FSRS weights to a review history.
+//!
+//! Each item's reviews are replayed through the memory model and the predicted
+//! retrievability at every long-term review is scored against whether the item
+//! was actually recalled (log-loss). The weights are then fitted by gradient
+//! descent (Adam), using central finite differences for the gradient.
+
+use super::*;
+use serde::{Deserialize, Serialize};
+
+/// A single review in the history of an item
+#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+pub struct Review {
+    pub grade: Grade,
+    /// number of days since the previous review of the same item
+    pub elapsed_days: Time,
+    /// whether it took place on the study day of the previous review, which makes it a
+    /// short term review
+    pub same_day: bool,
+}
+
+/// All reviews of a single item, oldest first
+pub type History = Vec<Review>;
+
+/// The result of fitting the weights to a review history
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Fit {
+    pub w: Weights,
+    pub loss_before: f32,
+    pub loss_after: f32,
+    /// number of reviews that contributed to the loss
+    pub n_reviews: usize,
+}
+
+/// Gradient descent settings
+#[derive(Clone, Copy, Debug)]
+pub struct Optimizer {
+    pub epochs: usize,
+    pub learning_rate: f32,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self {
+            epochs: 200,
+            learning_rate: 4e-2,
+        }
+    }
+}
+
+/// Step used for the finite difference gradient
+const EPSILON: f32 = 1e-3;
+
+impl Optimizer {
+    /// Fit the weights of `p`, including the decay of the forgetting curve, to `histories`
+    pub fn fit(&self, p: Parameters, histories: &[History]) -> Fit {
+        let n_reviews = histories.iter().map(model::n_scored).sum();
+        let loss_before = loss(&p, histories);
+        if n_reviews == 0 {
+            return Fit {
+                w: p.w,
+                loss_before,
+                loss_after: loss_before,
+                n_reviews,
+            };
+        }
+
+        // Adam
+        let (beta_1, beta_2) = (0.9f32, 0.999f32);
+        let mut p = p.clamped();
+        let mut m = [0.0f32; 21];
+        let mut v = [0.0f32; 21];
+        let mut best = (loss_before, p.w);
+        for t in 1..=self.epochs {
+            let g = gradient(&p, histories);
+            for k in 0..p.w.len() {
+                m[k] = beta_1 * m[k] + (1.0 - beta_1) * g[k];
+                v[k] = beta_2 * v[k] + (1.0 - beta_2) * g[k] * g[k];
+                let m_hat = m[k] / (1.0 - beta_1.powi(t as i32));
+                let v_hat = v[k] / (1.0 - beta_2.powi(t as i32));
+                p.w[k] -= self.learning_rate * m_hat / (v_hat.sqrt() + 1e-8);
+            }
+            p = p.clamped();
+            let l = loss(&p, histories);
+            if l < best.0 {
+                best = (l, p.w);
+            }
+        }
+
+        let (loss_after, w) = best;
+        Fit {
+            w,
+            loss_before,
+            loss_after,
+            n_reviews,
+        }
+    }
+}
+
+/// Mean log-loss of the predicted retrievability over all long-term reviews
+pub fn loss(p: &Parameters, histories: &[History]) -> f32 {
+    let (sum, n) = histories
+        .iter()
+        .map(|h| model::log_loss(p, h))
+        .fold((0.0f64, 0usize), |(sum, n), (s, k)| (sum + s, n + k));
+    if n == 0 {
+        0.0
+    } else {
+        (sum / n as f64) as f32
+    }
+}
+
+fn gradient(p: &Parameters, histories: &[History]) -> Weights {
+    std::array::from_fn(|k| {
+        let mut hi = *p;
+        let mut lo = *p;
+        hi.w[k] += EPSILON;
+        lo.w[k] -= EPSILON;
+        (loss(&hi, histories) - loss(&lo, histories)) / (2.0 * EPSILON)
+    })
+}
+
+/// Replays a review history through the memory model.
+mod model {
+    use super::*;
+
+    /// the number of reviews in `h` that contribute to the loss
+    pub fn n_scored(h: &History) -> usize {
+        h.iter().skip(1).filter(|r| !r.same_day).count()
+    }
+
+    /// summed log-loss of `h` and the number of scored reviews
+    pub fn log_loss(p: &Parameters, h: &History) -> (f64, usize) {
+        let mut state = None;
+        let (mut sum, mut n) = (0.0f64, 0usize);
+        for review in h {
+            if let Some(ModelData { s, .. }) = state {
+                if !review.same_day {
+                    let r = r(p, review.elapsed_days, s).clamp(1e-4, 1.0 - 1e-4);
+                    let y = if review.grade == Again { 0.0 } else { 1.0 };
+                    sum -= (y * r.ln() + (1.0 - y) * (1.0 - r).ln()) as f64;
+                    n += 1;
+                }
+            }
+            state = Some(next_state(
+                p,
+                state,
+                review.grade,
+                review.elapsed_days,
+                review.same_day,
+            ));
+        }
+        (sum, n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn review(grade: Grade, elapsed_days: Time) -> Review {
+        Review {
+            grade,
+            elapsed_days,
+            same_day: elapsed_days < 1.0,
+        }
+    }
+
+    #[test]
+    fn empty_history_has_no_loss() {
+        let fit = Optimizer::default().fit(Parameters::default(), &[]);
+        assert_eq!(fit.n_reviews, 0);
+        assert_eq!(fit.loss_before, 0.0);
+        assert_eq!(fit.w, W);
+    }
+
+    #[test]
+    fn fitting_does_not_increase_loss() {
+        // an item that is always forgotten after a few days
+        let histories: Vec<History> = (0..20)
+            .map(|_| {
+                vec![
+                    review(Good, 0.0),
+                    review(Again, 3.0),
+                    review(Good, 0.0),
+                    review(Again, 4.0),
+                    review(Again, 2.0),
+                ]
+            })
+            .collect();
+        let optimizer = Optimizer {
+            epochs: 20,
+            ..Default::default()
+        };
+        let fit = optimizer.fit(Parameters::default(), &histories);
+        assert_eq!(fit.n_reviews, 60);
+        assert!(fit.loss_after < fit.loss_before);
+        assert!(Parameters { w: fit.w }.is_clamped());
+    }
+}
@@ -0,0 +1,187 @@
+use super::*;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// The trainable weights of the FSRS algorithm
+pub type Weights = [f32; 21];
+
+/// Everything needed to evaluate the memory model. Each collection may carry its own
+/// parameters, usually fitted to its review history using [`crate::optimizer`].
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Parameters {
+    /// weights. Weights of older versions of the algorithm (17 or 19 values) are
+    /// accepted and completed with their implied values.
+    #[serde(deserialize_with = "deserialize_weights")]
+    pub w: Weights,
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Self { w: W }
+    }
+}
+
+/// The decay used by FSRS-4.5 and FSRS-5, which did not train it
+const FSRS5_DECAY: f32 = 0.5;
+
+/// The largest initial stability
+const INIT_S_MAX: Stability = 100.0;
+
+/// Legal range of each weight, mirroring the clamping of the reference implementation
+pub const W_BOUNDS: [(f32, f32); 21] = [
+    (S_MIN, INIT_S_MAX),
+    (S_MIN, INIT_S_MAX),
+    (S_MIN, INIT_S_MAX),
+    (S_MIN, INIT_S_MAX),
+    (D_MIN, D_MAX),
+    (0.001, 4.0),
+    (0.001, 4.0),
+    (0.001, 0.75),
+    (0.0, 4.5),
+    (0.0, 0.8),
+    (0.001, 3.5),
+    (0.001, 5.0),
+    (0.001, 0.25),
+    (0.001, 0.9),
+    (0.0, 4.0),
+    (0.0, 1.0),
+    (1.0, 6.0),
+    (0.0, 2.0),
+    (0.0, 2.0),
+    (0.0, 0.8),
+    (0.1, 0.8),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum ParameterError {
+    /// weight `w[i]` is nan or infinite
+    WeightNotFinite(usize),
+    /// the number of weights is not one of 17, 19 or 21
+    InvalidLength(usize),
+}
+
+impl std::fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParameterError::WeightNotFinite(i) => write!(f, "weight w[{i}] is not a finite number"),
+            ParameterError::InvalidLength(n) => {
+                write!(f, "expected 17, 19 or 21 weights, got {n}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParameterError {}
+
+impl Parameters {
+    /// Create parameters from weights, clamping them to their legal ranges
+    pub fn new(w: Weights) -> Result<Self, ParameterError> {
+        let p = Self { w };
+        p.validate()?;
+        Ok(p.clamped())
+    }
+
+    /// Create parameters from the weights of any FSRS version since 4.5. Missing
+    /// weights are filled in such that the algorithm behaves as that version did.
+    pub fn from_slice(w: &[f32]) -> Result<Self, ParameterError> {
+        Self::new(fill(w)?)
+    }
+
+    /// Check that the parameters can be used at all. Values outside of their legal range
+    /// are accepted, see [`Parameters::clamped`].
+    pub fn validate(&self) -> Result<(), ParameterError> {
+        if let Some(i) = self.w.iter().position(|w| !w.is_finite()) {
+            return Err(ParameterError::WeightNotFinite(i));
+        }
+        Ok(())
+    }
+
+    /// The parameters with every value clamped to its legal range
+    pub fn clamped(mut self) -> Self {
+        self.w
+            .iter_mut()
+            .zip(W_BOUNDS)
+            .for_each(|(w, (lo, hi))| *w = w.clamp(lo, hi));
+        self
+    }
+
+    /// Whether every value lies within its legal range
+    pub fn is_clamped(&self) -> bool {
+        *self == self.clamped()
+    }
+
+    /// decay, the exponent of the forgetting curve
+    pub fn decay(&self) -> f32 {
+        -self.w[20]
+    }
+
+    /// factor, chosen such that `r(s, s) == 0.9`
+    pub fn factor(&self) -> f32 {
+        0.9f32.powf(1.0 / self.decay()) - 1.0
+    }
+}
+
+/// Complete the weights of an older version of the algorithm
+fn fill(w: &[f32]) -> Result<Weights, ParameterError> {
+    Ok(match w.len() {
+        21 => w.try_into().unwrap(),
+        19 => std::array::from_fn(|i| match i {
+            19 => 0.0,
+            20 => FSRS5_DECAY,
+            i => w[i],
+        }),
+        17 => std::array::from_fn(|i| match i {
+            17..=19 => 0.0,
+            20 => FSRS5_DECAY,
+            i => w[i],
+        }),
+        n => return Err(ParameterError::InvalidLength(n)),
+    })
+}
+
+/// Clamping is left to the user of the parameters, such that it may be warned about
+fn deserialize_weights<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Weights, D::Error> {
+    let w = Vec::<f32>::deserialize(deserializer)?;
+    fill(&w).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_parameters_are_legal() {
+        let p = Parameters::default();
+        assert!(p.validate().is_ok());
+        assert!(p.is_clamped());
+        // decay of FSRS-5 gives the factor 19/81
+        let p = Parameters::from_slice(&W[..19]).unwrap();
+        assert!((p.factor() - 19.0 / 81.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn parameters_are_clamped() {
+        let mut w = W;
+        w[4] = 20.0;
+        w[7] = -1.0;
+        w[20] = 2.0;
+        let p = Parameters::new(w).unwrap();
+        assert_eq!(p.w[4], 10.0);
+        assert_eq!(p.w[7], 0.001);
+        assert_eq!(p.w[20], 0.8);
+
+        w[3] = f32::NAN;
+        assert_eq!(Parameters::new(w), Err(ParameterError::WeightNotFinite(3)));
+        assert_eq!(
+            Parameters::from_slice(&W[..3]),
+            Err(ParameterError::InvalidLength(3))
+        );
+    }
+
+    #[test]
+    fn weights_of_older_versions_are_filled() {
+        let p = Parameters::from_slice(&W[..17]).unwrap();
+        assert_eq!(&p.w[..17], &W[..17]);
+        assert_eq!(&p.w[17..], &[0.0, 0.0, 0.0, 0.5]);
+    }
+}
@@ -0,0 +1,617 @@
+//! Schedulers decide how the memory state of an item changes when it is reviewed, and
+//! how long to wait until the next review.
+//!
+//! Every scheduler keeps its state in a [`ModelData`], but not all of them read it as a
+//! stability and a difficulty:
+//! - [`Fsrs`]: `s` is the stability and `d` the difficulty of the memory
+//! - [`Sm2`]: `s` is the current interval and `d` the ease factor
+//! - [`Leitner`]: `s` is the current interval and `d` the box the item is in
+
+use super::*;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// Items whose stability exceeds this many days become [`Maturity::Tenured`]
+pub const TENURED_STABILITY: Stability = 100.0;
+
+/// Everything the scheduling of an item depends on
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct CardState {
+    pub maturity: Maturity,
+    pub s: Stability,
+    pub d: Difficulty,
+    pub last_review: OffsetDateTime,
+    /// number of reviews
+    pub reps: u32,
+    /// number of times the item was forgotten
+    pub lapses: u32,
+    /// the current step of a (re)learning item
+    pub step: u32,
+}
+
+impl CardState {
+    /// The state of an item that has never been reviewed, created at `now`
+    pub fn new(now: OffsetDateTime) -> Self {
+        Self {
+            maturity: Maturity::New,
+            s: 0.0,
+            d: 0.0,
+            last_review: now,
+            reps: 0,
+            lapses: 0,
+            step: 0,
+        }
+    }
+
+    /// The memory state, `None` if the item has never been scheduled
+    pub fn memory(&self) -> Option<ModelData> {
+        (self.maturity != Maturity::New).then_some(ModelData {
+            s: self.s,
+            d: self.d,
+        })
+    }
+
+    /// number of days between the last review and `now`
+    pub fn elapsed_days(&self, now: OffsetDateTime) -> Time {
+        (now - self.last_review).as_seconds_f32() / time::Duration::DAY.as_seconds_f32()
+    }
+}
+
+/// The shortest and longest interval, in days, of an item that is not (re)learning
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct IntervalBounds {
+    pub minimum: Interval,
+    pub maximum: Interval,
+}
+
+impl Default for IntervalBounds {
+    fn default() -> Self {
+        Self {
+            minimum: 1.0,
+            maximum: S_MAX,
+        }
+    }
+}
+
+impl IntervalBounds {
+    pub fn clamp(&self, i: Interval) -> Interval {
+        i.clamp(self.minimum, self.maximum)
+    }
+}
+
+/// Everything besides the scheduler that decides when an item is shown again
+#[derive(Clone, PartialEq, Debug)]
+pub struct Settings {
+    pub steps: Steps,
+    pub bounds: IntervalBounds,
+    /// see [`load_balance::WeekLoad`]
+    pub easy_days: load_balance::WeekLoad,
+    /// when days begin, see [`day::StudyDay`]
+    pub day: day::StudyDay,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            steps: Steps::default(),
+            bounds: IntervalBounds::default(),
+            easy_days: load_balance::EVEN_WEEK,
+            day: day::StudyDay::default(),
+        }
+    }
+}
+
+/// The state of an item after being reviewed with grade `g` at `now`, and when it is due
+/// next. New and forgotten items go through the steps of `settings` before `scheduler`
+/// decides their intervals, which are kept within the bounds of `settings`.
+///
+/// `fuzz` chooses where in its [`fuzz::range`] the interval ends up, `None` to not fuzz
+/// the interval. Given `due_on`, the number of items due on a date, the interval is
+/// instead moved to the least loaded day of its fuzz range, see [`load_balance`].
+pub fn schedule(
+    scheduler: &dyn Scheduler,
+    settings: &Settings,
+    state: &CardState,
+    g: Grade,
+    now: OffsetDateTime,
+    fuzz: Option<f32>,
+    due_on: Option<&dyn Fn(time::Date) -> u32>,
+) -> (CardState, OffsetDateTime) {
+    use Maturity::*;
+    let steps = &settings.steps;
+    // a review on the study day of the last one is a short term review
+    let same_day = settings.day.date(now) == settings.day.date(state.last_review);
+    let m = scheduler.next(state, g, now, same_day);
+    let elapsed = state.memory().map_or(0.0, |_| state.elapsed_days(now));
+    let mut next = CardState {
+        s: m.s,
+        d: m.d,
+        last_review: now,
+        reps: state.reps + 1,
+        step: 0,
+        ..*state
+    };
+    // a failed review on the same day is not a lapse, the user could be cramming
+    if matches!(state.maturity, Young | Tenured) && g == Again && !same_day {
+        next.lapses += 1;
+    }
+
+    let (maturity, step) = match (state.maturity, g) {
+        (New, _) => (Learning, Some((&steps.learning, 0))),
+        (Learning, _) => (Learning, Some((&steps.learning, state.step))),
+        (Relearning, _) => (Relearning, Some((&steps.relearning, state.step))),
+        (Young | Tenured, Again) => (Relearning, Some((&steps.relearning, 0))),
+        (maturity, _) => (maturity, None),
+    };
+    if let Some((step, delay)) = step.and_then(|(s, k)| steps::next(s, k, g)) {
+        next.maturity = maturity;
+        next.step = step;
+        return (next, now + delay);
+    }
+
+    next.maturity = match maturity {
+        _ if m.s > TENURED_STABILITY => Tenured,
+        Tenured => Tenured,
+        _ => Young,
+    };
+    let mut interval = settings.bounds.clamp(scheduler.interval(m));
+    if matches!(maturity, Learning | Relearning) {
+        // graduating items are not seen again on the same day
+        interval = interval.max(1.0);
+    }
+    let bounds = &settings.bounds;
+    let target = fuzz.map_or(interval, |f| {
+        bounds.clamp(fuzz::apply(interval, elapsed, f))
+    });
+    let interval = match due_on {
+        Some(due_on) if interval >= fuzz::MIN_FUZZ_INTERVAL => {
+            let (lo, hi) = fuzz::range(interval, elapsed);
+            let (lo, hi) = (bounds.clamp(lo), bounds.clamp(hi));
+            load_balance::balance(now, lo, hi, target, settings, due_on)
+        }
+        _ => target,
+    };
+    (next, now + time::Duration::DAY * interval)
+}
+
+/// When an item in `state` is due, had its last review been scheduled by `scheduler` with
+/// `settings`, e.g. after the desired retention was changed. The memory state is kept as
+/// is. `elapsed` are the days that had passed at the last review, such that the interval
+/// is fuzzed and load balanced (`due_on`) like when it was scored. `None` for items that
+/// are new or (re)learning, which follow their steps instead.
+pub fn reschedule(
+    scheduler: &dyn Scheduler,
+    settings: &Settings,
+    state: &CardState,
+    elapsed: Time,
+    fuzz: Option<f32>,
+    due_on: Option<&dyn Fn(time::Date) -> u32>,
+) -> Option<OffsetDateTime> {
+    use Maturity::*;
+    let (Young | Tenured, Some(m)) = (state.maturity, state.memory()) else {
+        return None;
+    };
+    let bounds = &settings.bounds;
+    let interval = bounds.clamp(scheduler.interval(m));
+    let target = fuzz.map_or(interval, |f| {
+        bounds.clamp(fuzz::apply(interval, elapsed, f))
+    });
+    let interval = match due_on {
+        Some(due_on) if interval >= fuzz::MIN_FUZZ_INTERVAL => {
+            let (lo, hi) = fuzz::range(interval, elapsed);
+            let (lo, hi) = (bounds.clamp(lo), bounds.clamp(hi));
+            load_balance::balance(state.last_review, lo, hi, target, settings, due_on)
+        }
+        _ => target,
+    };
+    Some(state.last_review + time::Duration::DAY * interval)
+}
+
+pub trait Scheduler {
+    /// The state of an item in `state` after being reviewed with grade `g` at `now`,
+    /// `same_day` if that is on the study day of its last review
+    fn next(&self, state: &CardState, g: Grade, now: OffsetDateTime, same_day: bool) -> ModelData;
+    /// The number of days to wait after a review that left the item in state `m`
+    fn interval(&self, m: ModelData) -> Interval;
+}
+
+/// The schedulers that can be chosen in config
+#[derive(Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SchedulerKind {
+    #[default]
+    Fsrs,
+    Sm2,
+    Leitner,
+}
+
+/// The FSRS algorithm, showing items once their retrievability has dropped to
+/// `desired_retention`
+#[derive(Clone, Copy, Debug)]
+pub struct Fsrs {
+    pub p: Parameters,
+    pub desired_retention: Retrievability,
+}
+
+impl Scheduler for Fsrs {
+    fn next(&self, state: &CardState, g: Grade, now: OffsetDateTime, same_day: bool) -> ModelData {
+        next_state(
+            &self.p,
+            state.memory(),
+            g,
+            state.elapsed_days(now),
+            same_day,
+        )
+    }
+
+    fn interval(&self, m: ModelData) -> Interval {
+        i(&self.p, self.desired_retention, m.s)
+    }
+}
+
+/// The SM-2 algorithm of SuperMemo, with the ease adjustments used by Anki. It does not
+/// look at the time since the last review.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Sm2;
+
+impl Sm2 {
+    pub const INITIAL_EASE: f32 = 2.5;
+    pub const MIN_EASE: f32 = 1.3;
+}
+
+impl Scheduler for Sm2 {
+    fn next(&self, state: &CardState, g: Grade, _now: OffsetDateTime, _: bool) -> ModelData {
+        let Some(ModelData { s, d: ease }) = state.memory() else {
+            let s = if g == Easy { 4.0 } else { 1.0 };
+            return ModelData {
+                s,
+                d: Self::INITIAL_EASE,
+            };
+        };
+        let ease_change = match g {
+            Again => -0.2,
+            Hard => -0.15,
+            Good => 0.0,
+            Easy => 0.15,
+        };
+        let ease = (ease + ease_change).max(Self::MIN_EASE);
+        // the second interval is always 6 days
+        let good = if s < 6.0 { 6.0 } else { s * ease };
+        let s = match g {
+            Again => 1.0,
+            Hard => (s * 1.2).max(s + 1.0),
+            Good => good,
+            Easy => good * 1.3,
+        };
+        ModelData { s, d: ease }
+    }
+
+    fn interval(&self, m: ModelData) -> Interval {
+        m.s
+    }
+}
+
+/// Leitner boxes. Each box has a fixed interval. A correct answer moves the item up a
+/// box (two for Easy), a wrong one moves it back to the first box.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Leitner {
+    /// the interval of each box in days
+    pub intervals: Vec<Interval>,
+}
+
+impl Default for Leitner {
+    fn default() -> Self {
+        Self {
+            intervals: vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0],
+        }
+    }
+}
+
+impl Leitner {
+    /// the interval of box `b`, counting from 1
+    fn box_interval(&self, b: usize) -> Interval {
+        let last = self.intervals.len().saturating_sub(1);
+        self.intervals
+            .get(b.saturating_sub(1).min(last))
+            .copied()
+            .unwrap_or(1.0)
+    }
+}
+
+impl Scheduler for Leitner {
+    fn next(&self, state: &CardState, g: Grade, _now: OffsetDateTime, _: bool) -> ModelData {
+        let current = state.memory().map_or(0, |m| m.d.max(1.0) as usize);
+        let b = match g {
+            Again => 1,
+            Hard => current.max(1),
+            Good => current + 1,
+            Easy => current + 2,
+        }
+        .min(self.intervals.len().max(1));
+        ModelData {
+            s: self.box_interval(b),
+            d: b as f32,
+        }
+    }
+
+    fn interval(&self, m: ModelData) -> Interval {
+        self.box_interval(m.d as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::Duration;
+
+    fn new_card() -> CardState {
+        CardState::new(OffsetDateTime::UNIX_EPOCH)
+    }
+
+    /// Review an item with `grades`, each `days` after the previous review
+    fn replay(scheduler: &dyn Scheduler, grades: &[Grade], days: i64) -> ModelData {
+        let mut state = new_card();
+        for &g in grades {
+            let now = state.last_review + Duration::days(days);
+            let m = scheduler.next(&state, g, now, days == 0);
+            state = CardState {
+                maturity: Maturity::Young,
+                s: m.s,
+                d: m.d,
+                last_review: now,
+                ..state
+            };
+        }
+        state.memory().unwrap()
+    }
+
+    #[test]
+    fn fsrs_follows_the_memory_model() {
+        let fsrs = Fsrs {
+            p: Parameters::default(),
+            desired_retention: 0.9,
+        };
+        let m = replay(&fsrs, &[Good, Good], 3);
+        let expected = next_state(
+            &fsrs.p,
+            Some(next_state(&fsrs.p, None, Good, 0.0, true)),
+            Good,
+            3.0,
+            false,
+        );
+        assert_eq!((m.s, m.d), (expected.s, expected.d));
+        assert!((fsrs.interval(m) - m.s).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sm2_intervals() {
+        let m = replay(&Sm2, &[Good, Good, Good], 1);
+        assert_eq!((m.s, m.d), (15.0, 2.5));
+        let m = replay(&Sm2, &[Good, Good, Good, Again], 1);
+        assert_eq!((m.s, m.d), (1.0, 2.3));
+        let m = replay(&Sm2, &[Again; 10], 1);
+        assert_eq!(m.d, Sm2::MIN_EASE);
+    }
+
+    #[test]
+    fn leitner_boxes() {
+        let leitner = Leitner::default();
+        let m = replay(&leitner, &[Good, Good, Easy], 1);
+        assert_eq!((m.s, m.d), (8.0, 4.0));
+        let m = replay(&leitner, &[Good, Good, Hard, Again], 1);
+        assert_eq!((m.s, m.d), (1.0, 1.0));
+        let m = replay(&leitner, &[Easy; 10], 1);
+        assert_eq!(leitner.interval(m), 32.0);
+    }
+
+    fn days(from: OffsetDateTime, to: OffsetDateTime) -> f32 {
+        (to - from).as_seconds_f32() / 86400.0
+    }
+
+    #[test]
+    fn schedule_transitions() {
+        use Maturity::*;
+        let fsrs = Fsrs {
+            p: Parameters::default(),
+            desired_retention: 0.9,
+        };
+        let steps = Settings {
+            steps: Steps {
+                learning: vec![Step(Duration::minutes(1)), Step(Duration::minutes(10))],
+                relearning: vec![Step(Duration::minutes(10))],
+            },
+            ..Default::default()
+        };
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::days(1);
+
+        // a new item goes through the learning steps
+        let (state, due) = schedule(&fsrs, &steps, &new_card(), Again, now, None, None);
+        assert_eq!((state.maturity, state.step, state.reps), (Learning, 0, 1));
+        assert_eq!((state.s, due), (W[0], now + Duration::minutes(1)));
+        let now = due;
+        let (state, due) = schedule(&fsrs, &steps, &state, Good, now, None, None);
+        assert_eq!((state.maturity, state.step), (Learning, 1));
+        assert_eq!(due, now + Duration::minutes(10));
+        let now = due;
+        let (state, due) = schedule(&fsrs, &steps, &state, Good, now, None, None);
+        assert_eq!((state.maturity, state.last_review), (Young, now));
+        assert!(days(now, due) >= 1.0);
+
+        // or skips them
+        let (easy, _) = schedule(&fsrs, &steps, &new_card(), Easy, now, None, None);
+        assert_eq!(easy.maturity, Young);
+        let (again, due) = schedule(
+            &fsrs,
+            &Settings::default(),
+            &new_card(),
+            Again,
+            now,
+            None,
+            None,
+        );
+        assert_eq!((again.maturity, due), (Young, now + Duration::DAY));
+
+        // failing on the same day is not a lapse
+        let (same_day, _) = schedule(
+            &fsrs,
+            &steps,
+            &state,
+            Again,
+            now + Duration::hours(1),
+            None,
+            None,
+        );
+        assert_eq!((same_day.maturity, same_day.lapses), (Relearning, 0));
+        // unless the day rolled over in between, here at 1am
+        let rollover = Settings {
+            day: day::StudyDay::new("UTC", 1).unwrap(),
+            ..steps.clone()
+        };
+        let (next_day, _) = schedule(
+            &fsrs,
+            &rollover,
+            &state,
+            Again,
+            now + Duration::hours(1),
+            None,
+            None,
+        );
+        assert_eq!((next_day.maturity, next_day.lapses), (Relearning, 1));
+        // which also decides whether the memory model sees a short term review
+        let t = now + Duration::hours(1);
+        assert_eq!(same_day.s, fsrs.next(&state, Again, t, true).s);
+        assert_eq!(next_day.s, fsrs.next(&state, Again, t, false).s);
+        assert_ne!(same_day.s, next_day.s);
+        let later = now + Duration::days(5);
+        let (lapsed, due) = schedule(&fsrs, &steps, &state, Again, later, None, None);
+        assert_eq!((lapsed.maturity, lapsed.lapses), (Relearning, 1));
+        assert_eq!(due, later + Duration::minutes(10));
+        assert!(lapsed.s < state.s);
+        let (relearnt, _) = schedule(&fsrs, &steps, &lapsed, Good, due, None, None);
+        assert_eq!((relearnt.maturity, relearnt.lapses), (Young, 1));
+
+        // fuzz only moves the due date
+        let later = now + Duration::days(3);
+        let (a, due_a) = schedule(&fsrs, &steps, &state, Good, later, None, None);
+        let (b, due_b) = schedule(&fsrs, &steps, &state, Good, later, Some(0.999), None);
+        assert_eq!(a, b);
+        let (_, hi) = fuzz::range(days(later, due_a), 3.0);
+        assert_eq!(due_b, later + Duration::days(hi as i64));
+
+        // a long interval makes the item tenured
+        let state = CardState {
+            s: 90.0,
+            d: 2.5,
+            ..state
+        };
+        let (state, _) = schedule(&Sm2, &steps, &state, Good, later, None, None);
+        assert_eq!(state.maturity, Tenured);
+    }
+
+    #[test]
+    fn intervals_are_bounded() {
+        let fsrs = Fsrs {
+            p: Parameters::default(),
+            desired_retention: 0.9,
+        };
+        let settings = Settings {
+            bounds: IntervalBounds {
+                minimum: 2.0,
+                maximum: 5.0,
+            },
+            ..Default::default()
+        };
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let (_, due) = schedule(&fsrs, &settings, &new_card(), Again, now, None, None);
+        assert_eq!(due, now + Duration::days(2));
+        let (_, due) = schedule(&fsrs, &settings, &new_card(), Easy, now, Some(0.999), None);
+        assert_eq!(due, now + Duration::days(5));
+    }
+
+    #[test]
+    fn items_are_rescheduled() {
+        let fsrs = |desired_retention| Fsrs {
+            p: Parameters::default(),
+            desired_retention,
+        };
+        let state = CardState {
+            maturity: Maturity::Young,
+            s: 10.0,
+            d: 5.0,
+            ..new_card()
+        };
+        let settings = Settings::default();
+        let due = |r| reschedule(&fsrs(r), &settings, &state, 0.0, None, None).unwrap();
+        assert_eq!(due(0.9), state.last_review + Duration::days(10));
+        assert!(due(0.95) < due(0.9));
+        // fuzzed like when it was scored
+        let fuzzed = reschedule(&fsrs(0.9), &settings, &state, 0.0, Some(0.0), None).unwrap();
+        assert_eq!(fuzzed, state.last_review + Duration::days(8));
+        let fuzzed = reschedule(&fsrs(0.9), &settings, &state, 9.0, Some(0.0), None).unwrap();
+        assert_eq!(fuzzed, state.last_review + Duration::days(10));
+        // and load balanced like when it was scored
+        let due_on = |date: time::Date| date.day() as u32 % 3;
+        let now = state.last_review + Duration::days(10);
+        let (scored, due) = schedule(
+            &fsrs(0.9),
+            &settings,
+            &state,
+            Good,
+            now,
+            Some(0.5),
+            Some(&due_on),
+        );
+        let rescheduled = reschedule(
+            &fsrs(0.9),
+            &settings,
+            &scored,
+            10.0,
+            Some(0.5),
+            Some(&due_on),
+        );
+        assert_eq!(rescheduled, Some(due));
+        // (re)learning items follow their steps
+        let learning = CardState {
+            maturity: Maturity::Learning,
+            ..state
+        };
+        assert_eq!(
+            reschedule(&fsrs(0.9), &settings, &learning, 0.0, None, None),
+            None
+        );
+    }
+
+    #[test]
+    fn intervals_are_load_balanced() {
+        let sm2 = Sm2;
+        let state = CardState {
+            maturity: Maturity::Young,
+            s: 10.0,
+            d: 2.5,
+            ..new_card()
+        };
+        let now = state.last_review + Duration::days(10);
+        // the good interval of 25 days may move to 22..=28, day 27 is free
+        let busy = |date: time::Date| {
+            if date == (now + Duration::days(27)).date() {
+                0
+            } else {
+                3
+            }
+        };
+        let (_, due) = schedule(
+            &sm2,
+            &Settings::default(),
+            &state,
+            Good,
+            now,
+            Some(0.5),
+            Some(&busy),
+        );
+        assert_eq!(due, now + Duration::days(27));
+        // unless the load balancer is off
+        let (_, due) = schedule(&sm2, &Settings::default(), &state, Good, now, None, None);
+        assert_eq!(due, now + Duration::days(25));
+    }
+}
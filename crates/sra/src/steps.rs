@@ -0,0 +1,150 @@
+//! Learning and relearning steps.
+//!
+//! New items, and items that were forgotten, are first shown again after a few short
+//! delays (the steps) before their intervals are decided by a [`crate::Scheduler`].
+//! Again moves an item back to the first step, Hard repeats the current step, Good moves
+//! it to the next step and Easy, or Good on the last step, graduates it.
+
+use serde::{Deserialize, Serialize};
+use time::Duration;
+
+/// The delay of a single step. Written as a whole number followed by a unit, one of `s`,
+/// `m`, `h` or `d`, e.g. `10m`, of at most [`crate::S_MAX`] days.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Step(pub Duration);
+
+/// The steps that items go through. No steps means that items graduate immediately.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Steps {
+    /// the steps of new items
+    pub learning: Vec<Step>,
+    /// the steps of items that were forgotten
+    pub relearning: Vec<Step>,
+}
+
+/// The step an item moves to from step `k` of `steps` when graded with `g`, together with
+/// its delay. `None` if the item graduates.
+pub(crate) fn next(steps: &[Step], k: u32, g: crate::model::Grade) -> Option<(u32, Duration)> {
+    use crate::model::Grade::*;
+    let k = (k as usize).min(steps.len().checked_sub(1)?);
+    match g {
+        Again => Some((0, steps[0].0)),
+        Hard => Some((k as u32, hard_delay(steps, k))),
+        Good => steps.get(k + 1).map(|s| (k as u32 + 1, s.0)),
+        Easy => None,
+    }
+}
+
+/// Hard repeats the current step. On the first step the delay lies halfway to the next
+/// step, or is 50% longer (at most a day more) if there is only one step.
+fn hard_delay(steps: &[Step], k: usize) -> Duration {
+    match (k, steps.get(1)) {
+        (0, Some(next)) => (steps[0].0 + next.0) / 2,
+        (0, None) => std::cmp::min(steps[0].0 * 1.5f32, steps[0].0 + Duration::DAY),
+        _ => steps[k].0,
+    }
+}
+
+impl std::str::FromStr for Step {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unit_start = s
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or(format!("step `{s}` is missing a unit (s, m, h or d)"))?;
+        let (n, unit) = s.split_at(unit_start);
+        let n: i64 = n
+            .parse()
+            .map_err(|_| format!("step `{s}` should start with a whole number"))?;
+        let unit_seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            _ => return Err(format!("unknown unit `{unit}` in step `{s}`")),
+        };
+        // no longer than the longest interval, such that it can be added to a date
+        let d = n
+            .checked_mul(unit_seconds)
+            .map(Duration::seconds)
+            .filter(|d| *d <= Duration::days(crate::S_MAX as i64))
+            .ok_or(format!("step `{s}` is longer than {} days", crate::S_MAX))?;
+        if d.is_zero() {
+            return Err(format!("step `{s}` should be longer than zero"));
+        }
+        Ok(Step(d))
+    }
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = self.0.whole_seconds();
+        match s {
+            _ if s % 86400 == 0 => write!(f, "{}d", s / 86400),
+            _ if s % 3600 == 0 => write!(f, "{}h", s / 3600),
+            _ if s % 60 == 0 => write!(f, "{}m", s / 60),
+            _ => write!(f, "{s}s"),
+        }
+    }
+}
+
+impl Serialize for Step {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Step {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::Grade::*;
+
+    fn steps(s: &[&str]) -> Vec<Step> {
+        s.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parse_steps() {
+        assert_eq!(
+            steps(&["90s", "10m", "1h", "2d"])[1],
+            Step(Duration::minutes(10))
+        );
+        assert!("10".parse::<Step>().is_err());
+        assert!("10w".parse::<Step>().is_err());
+        assert!("0m".parse::<Step>().is_err());
+        assert!("99999999999999d".parse::<Step>().is_err());
+        assert!("999999999999999d".parse::<Step>().is_err());
+        let shown: Vec<String> = steps(&["90s", "120m", "24h"])
+            .iter()
+            .map(Step::to_string)
+            .collect();
+        assert_eq!(shown, ["90s", "2h", "1d"]);
+    }
+
+    #[test]
+    fn step_transitions() {
+        let s = steps(&["1m", "10m"]);
+        assert_eq!(next(&s, 0, Again), Some((0, Duration::minutes(1))));
+        assert_eq!(next(&s, 0, Hard), Some((0, Duration::seconds(330))));
+        assert_eq!(next(&s, 0, Good), Some((1, Duration::minutes(10))));
+        assert_eq!(next(&s, 1, Hard), Some((1, Duration::minutes(10))));
+        assert_eq!(next(&s, 1, Good), None);
+        assert_eq!(next(&s, 0, Easy), None);
+
+        let s = steps(&["10m"]);
+        assert_eq!(next(&s, 0, Hard), Some((0, Duration::minutes(15))));
+        assert_eq!(next(&s, 0, Good), None);
+        // no steps, or steps that were removed since
+        assert_eq!(next(&[], 0, Again), None);
+        assert_eq!(next(&s, 3, Hard), Some((0, Duration::minutes(15))));
+    }
+}
@@ -98,8 +98,10 @@ edit() {
   "$SPBASEDCTL_BIN" items edit  --data "$FLASHCARD" "$id"
 }
 review() {
-  N_NEW=$("$SPBASEDCTL_BIN" review query-count new)
-  N_DUE=$("$SPBASEDCTL_BIN" review query-count due)
+  # the number of items, or the quota left today if that is lower
+  AVAILABLE='[.count, (.remaining // .count)] | min'
+  N_NEW=$("$SPBASEDCTL_BIN" review query-count new | jq "$AVAILABLE")
+  N_DUE=$("$SPBASEDCTL_BIN" review query-count due | jq "$AVAILABLE")
   gum log --level info "You have $N_DUE flashcards that are due and $N_NEW that are new"
 
   if [[ $N_NEW == "0" && $N_DUE == "0" ]]; then
@@ -146,11 +148,17 @@ review() {
   fi
   glow <<< "$ANSWER"
 
-  # prompt for how well it went
-  AGAIN="again (could not answer)"
-  HARD="hard (could answer with difficulty)"
-  GOOD="good (could answer)"
-  EASY="easy (could answer easily)"
+  # prompt for how well it went, showing when the card would be seen again
+  PREVIEW=$("$SPBASEDCTL_BIN" review preview "$FLASHCARD_ID")
+  interval() {
+    jq -r --arg grade "$1" \
+      '.[$grade].interval | if . == null then "soon" elif . < 1 then "\(. * 1440 | round)m" else "\(round)d" end' \
+      <<< "$PREVIEW"
+  }
+  AGAIN="again (could not answer) [$(interval Again)]"
+  HARD="hard (could answer with difficulty) [$(interval Hard)]"
+  GOOD="good (could answer) [$(interval Good)]"
+  EASY="easy (could answer easily) [$(interval Easy)]"
 
   RESULT=$(gum choose \
     --header "How easy was it to answer the prompt?" \
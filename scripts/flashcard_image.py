@@ -135,14 +135,20 @@ def spbased_add(questions: List[str], answers: List[str]):
     )
 
 
-def spbased_n_due_new():
-    n_due = spbased_command(
-        ["review", "query-count", "due", f"--filter=model=='{SPBASED_MODEL}'"]
-    )
-    n_new = spbased_command(
-        ["review", "query-count", "new", f"--filter=model=='{SPBASED_MODEL}'"]
+def spbased_n_available(kind: str) -> int:
+    """the number of items, or the quota left today if that is lower"""
+    counts = json.loads(
+        spbased_command(
+            ["review", "query-count", kind, f"--filter=model=='{SPBASED_MODEL}'"]
+        )
     )
-    return (n_due, n_new)
+    if counts["remaining"] is None:
+        return counts["count"]
+    return min(counts["count"], counts["remaining"])
+
+
+def spbased_n_due_new():
+    return (spbased_n_available("due"), spbased_n_available("new"))
 
 
 def spbased_get_new():
@@ -161,6 +167,18 @@ def spbased_review_score(id: str, score: str):
     return spbased_command(["review", "score", id, score])
 
 
+def spbased_review_preview(id: str):
+    return json.loads(spbased_command(["review", "preview", id]))
+
+
+def format_interval(interval: Optional[float]) -> str:
+    if interval is None:
+        return "soon"
+    if interval < 1:
+        return f"{round(interval * 1440)}m"
+    return f"{round(interval)}d"
+
+
 ###############################################################################
 # imv helpers
 ###############################################################################
@@ -222,7 +240,6 @@ def edit():
 @app.command()
 def review():
     (n_due, n_new) = spbased_n_due_new()
-    (n_due, n_new) = (int(n_due), int(n_new))
     gum_log(f"You have {n_due} image flashcards that are due and {n_new} that are new")
 
     if not n_due and not n_new:
@@ -258,8 +275,13 @@ def review():
     gum_log("showing question and answer", level="info")
     imv_open(answer)
 
-    # grade
-    res = gum_choose([f"{c}" for c in CHOICES], GOOD)
+    # grade, showing when the card would be seen again
+    preview = spbased_review_preview(id)
+    choices = [
+        f"{c} [{format_interval(preview[grade]['interval'])}]"
+        for (c, grade) in zip(CHOICES, ["Again", "Hard", "Good", "Easy"])
+    ]
+    res = gum_choose(choices, choices[CHOICES.index(GOOD)])
 
     if res is None:
         return